@@ -0,0 +1,157 @@
+use std::fs;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use board::epd::Epd;
+use board::perft_parallel;
+
+// Used when no file is given
+const BUNDLED_SUITE: &str = include_str!("../../suites/perft.epd");
+
+const USAGE: &str = "Usage: perft_suite [--depth MAX] [--threads COUNT] [FILE]
+Runs every position of a perft EPD suite (D1 20 ;D2 400 ...) up to MAX plies, all of them by default.
+Without a FILE the bundled suite is used. COUNT 0 uses every core, which is the default.";
+
+struct Options {
+    max_depth: u32,
+    threads: usize,
+    file: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options { max_depth: u32::MAX, threads: 0, file: None };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" | "-d" => {
+                let value = args.next().ok_or("--depth needs a value")?;
+                options.max_depth = value.parse().map_err(|_| format!("bad depth \"{}\"", value))?;
+            }
+            "--threads" | "-t" => {
+                let value = args.next().ok_or("--threads needs a value")?;
+                options.threads = value.parse().map_err(|_| format!("bad thread count \"{}\"", value))?;
+            }
+            "--help" | "-h" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if options.file.is_none() => options.file = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+// Positions with their line numbers, blank lines and lines starting with # are skipped
+fn parse_suite(text: &str) -> Result<Vec<(usize, Epd)>, String> {
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| line.parse().map(|epd| (index + 1, epd)).map_err(|err| format!("line {}: {}", index + 1, err)))
+        .collect()
+}
+
+struct Outcome {
+    nodes: u64,
+    elapsed: Duration,
+    mismatches: Vec<(u32, u64, u64)>, // depth, expected, found
+}
+
+// Every depth the position lists, up to max_depth
+fn run_position(epd: &Epd, max_depth: u32, threads: usize) -> Outcome {
+    let mut outcome = Outcome { nodes: 0, elapsed: Duration::ZERO, mismatches: Vec::new() };
+    for &(depth, expected) in epd.perft.iter().filter(|&&(depth, _)| depth <= max_depth) {
+        let start_time = Instant::now();
+        let found = perft_parallel(&epd.position, depth, threads, None).total.total_count;
+        outcome.elapsed += start_time.elapsed();
+        outcome.nodes += found;
+        if found != expected {
+            outcome.mismatches.push((depth, expected, found));
+        }
+    }
+    outcome
+}
+
+fn nodes_per_second(nodes: u64, elapsed: Duration) -> u64 {
+    (nodes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}", message);
+            }
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let text = match &options.file {
+        Some(file) => match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("Couldn't read {}: {}", file, err);
+                return ExitCode::from(2);
+            }
+        },
+        None => BUNDLED_SUITE.to_string(),
+    };
+    let suite = match parse_suite(&text) {
+        Ok(suite) => suite,
+        Err(message) => {
+            eprintln!("Invalid suite, {}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    let (mut failed, mut nodes, mut elapsed) = (0, 0, Duration::ZERO);
+    for (line, epd) in &suite {
+        let name = epd.id.clone().unwrap_or_else(|| epd.position.to_fen());
+        let outcome = run_position(epd, options.max_depth, options.threads);
+        nodes += outcome.nodes;
+        elapsed += outcome.elapsed;
+
+        let status = if outcome.mismatches.is_empty() { "ok" } else { "FAILED" };
+        println!("{:>4} {:<30} {:<6} {:>12} nodes {:>9.3}s {:>12} nps", line, name, status,
+                 outcome.nodes, outcome.elapsed.as_secs_f64(), nodes_per_second(outcome.nodes, outcome.elapsed));
+        for (depth, expected, found) in &outcome.mismatches {
+            println!("     D{} expected {} but found {}", depth, expected, found);
+        }
+        if !outcome.mismatches.is_empty() {
+            failed += 1;
+        }
+    }
+
+    println!("{} of {} positions passed, {} nodes in {:.3}s, {} nps", suite.len() - failed, suite.len(),
+             nodes, elapsed.as_secs_f64(), nodes_per_second(nodes, elapsed));
+    if failed == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_args, parse_suite, run_position, BUNDLED_SUITE};
+
+    #[test]
+    fn test_bundled_suite() {
+        let suite = parse_suite(BUNDLED_SUITE).unwrap();
+        assert_eq!(suite.len(), 22);
+        assert!(suite.iter().all(|(_, epd)| !epd.perft.is_empty() && epd.id.is_some()));
+        for (line, epd) in &suite {
+            assert!(run_position(epd, 3, 0).mismatches.is_empty(), "line {}", line);
+        }
+
+        // A wrong count is reported with its depth
+        let (_, epd) = &parse_suite("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1 ;D1 14 ;D2 190 ;D3 2812").unwrap()[0];
+        assert_eq!(run_position(epd, 3, 2).mismatches, vec![(2, 190, 191)]);
+        assert!(parse_suite("# comment\n\n8/8/8/8/8 w - - ;D1 1").err().is_some_and(|message| message.starts_with("line 3")));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |text: &str| text.split_whitespace().map(str::to_string).collect::<Vec<_>>().into_iter();
+        let options = parse_args(args("--depth 4 -t 8 suite.epd")).unwrap();
+        assert_eq!((options.max_depth, options.threads, options.file.as_deref()), (4, 8, Some("suite.epd")));
+        let options = parse_args(args("")).unwrap();
+        assert_eq!((options.max_depth, options.threads, options.file), (u32::MAX, 0, None));
+        for text in ["--depth", "--depth x", "--bogus", "a.epd b.epd"] {
+            assert!(parse_args(args(text)).is_err(), "{}", text);
+        }
+    }
+}
@@ -1,16 +1,13 @@
-const WHITE_PIECES: usize = 6;
-const BLACK_PIECES: usize = 7;
-
-pub const PAWN: usize = 0;
-pub const KNIGHT: usize = 1;
-pub const BISHOP: usize = 2;
-pub const ROOK: usize = 3;
-pub const QUEEN: usize = 4;
-pub const KING: usize = 5;
+use crate::Error;
+use crate::magic;
+use crate::tables::{between, king_attacks, knight_attacks, pawn_attacks};
+use crate::types::{squares, Color, Piece, PieceKind, Square};
+use crate::zobrist::piece_key;
 
 #[derive(Clone)]
 pub struct Board {
-    pub bitboards: [u64; 8],
+    pub bitboards: [u64; 8], // one per PieceKind, then one per Color
+    pub hash: u64, // zobrist key of the pieces, kept up to date by put_piece and remove_piece
 }
 
 pub fn print_bitboard(bitboard: u64, on: char, off: char) {
@@ -29,99 +26,76 @@ pub fn print_bitboard(bitboard: u64, on: char, off: char) {
     println!();
 }
 
-pub fn square_from_algebraic(sqr: &str) -> u64 {
-    let mut file: u64 = 0;
-    let mut rank: u64 = 0;
-
-    assert_eq!(sqr.len(), 2, "Invalid algebraic square format");
-    assert!(sqr.chars().next().unwrap_or('z').is_alphabetic(), "Invalid algebraic square format");
-    assert!(sqr.chars().nth(1).unwrap_or('z').is_numeric(), "Invalid algebraic square format");
-
-    // Parse the square
-    for c in sqr.chars() {
-        match c {
-            'a' => file = 0,
-            'b' => file = 1,
-            'c' => file = 2,
-            'd' => file = 3,
-            'e' => file = 4,
-            'f' => file = 5,
-            'g' => file = 6,
-            'h' => file = 7,
-            '1' => rank = 0,
-            '2' => rank = 1,
-            '3' => rank = 2,
-            '4' => rank = 3,
-            '5' => rank = 4,
-            '6' => rank = 5,
-            '7' => rank = 6,
-            '8' => rank = 7,
-            _ => { panic!("Invalid square"); }
-        }
-    }
+pub fn square_from_algebraic(sqr: &str) -> Square {
+    try_square_from_algebraic(sqr).unwrap_or_else(|err| panic!("{}", err))
+}
 
-    rank * 8 + file
+pub fn try_square_from_algebraic(sqr: &str) -> Result<Square, Error> {
+    sqr.parse()
 }
 
-pub fn square_to_algebraic(square: u64) -> String {
-    assert!(square <= 64, "Invalid square");
-
-    format!("{}{}", match square % 8 { // square % 8 is the file
-        0 => 'a',
-        1 => 'b',
-        2 => 'c',
-        3 => 'd',
-        4 => 'e',
-        5 => 'f',
-        6 => 'g',
-        7 => 'h',
-        _ => { unreachable!("Invalid square"); }
-    }, square / 8 + 1) // square / 8 is the rank
+// Bitboard of the named squares, for tests
+#[cfg(test)]
+pub(crate) fn bits(names: &[&str]) -> u64 {
+    names.iter().fold(0, |bits, name| bits | square_from_algebraic(name).bit())
+}
+
+pub fn square_to_algebraic(square: Square) -> String {
+    square.to_string()
 }
 
 pub fn create_board() -> Board {
     Board {
         bitboards: [0x0; 8],
+        hash: 0,
     }
 }
 
 pub fn create_board_from_string(string: &str) -> Board {
-    let mut board = Board {
-        bitboards: [0x0; 8],
-    };
+    try_create_board_from_string(string).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub fn try_create_board_from_string(string: &str) -> Result<Board, Error> {
+    let mut board = create_board();
 
     // Parse the fen
     let ranks = string.split('/');
-    assert_eq!(string.split('/').count(), 8, "Invalid fen format");
+    let rank_count = string.split('/').count();
+    if rank_count != 8 {
+        return Err(Error::InvalidRankCount(rank_count));
+    }
 
-    let mut current_rank: u32 = 8;
+    let mut current_rank: usize = 8;
     for rank in ranks {
-        let mut current_file: u32 = 0;
+        let mut current_file: usize = 0;
 
         current_rank -= 1;
         for file in rank.chars() {
-            if file.is_numeric() {
-                current_file += file.to_digit(10).unwrap();
+            if let Some(empty) = file.to_digit(10) {
+                if empty == 0 || empty > 8 {
+                    return Err(Error::InvalidPiece(file));
+                }
+                current_file += empty as usize;
+                if current_file > 8 {
+                    return Err(Error::RankOverfull(current_rank as u32 + 1));
+                }
                 continue;
             }
-            assert!(current_file <= 8, "Invalid fen format");
-            let square = current_rank * 8 + current_file;
-
-            board.bitboards[match file {
-                'p' | 'P' => 0,
-                'n' | 'N' => 1,
-                'b' | 'B' => 2,
-                'r' | 'R' => 3,
-                'q' | 'Q' => 4,
-                'k' | 'K' => 5,
-                _ => { panic!("Invalid fen format"); }
-            }] |= 1u64 << square;
-            board.bitboards[if file.is_uppercase() { WHITE_PIECES } else { BLACK_PIECES }] |= 1u64 << square;
+            if current_file >= 8 {
+                return Err(Error::RankOverfull(current_rank as u32 + 1));
+            }
+
+            let piece = Piece::from_char(file).ok_or(Error::InvalidPiece(file))?;
+            board.put_piece(piece, Square::from_index(current_rank * 8 + current_file));
             current_file += 1;
         }
+
+        if current_file != 8 {
+            return Err(Error::RankIncomplete(current_rank as u32 + 1));
+        }
     }
 
-    board
+    Ok(board)
 }
 
 pub fn create_default_board() -> Board {
@@ -130,44 +104,202 @@ pub fn create_default_board() -> Board {
 
 impl Board {
     pub fn all_pieces(&self) -> u64 {
-        self.bitboards[WHITE_PIECES] | self.bitboards[BLACK_PIECES]
+        self.bitboards[Color::White.bitboard()] | self.bitboards[Color::Black.bitboard()]
     }
 
     pub fn white_pieces(&self) -> u64 {
-        self.bitboards[WHITE_PIECES]
+        self.bitboards[Color::White.bitboard()]
     }
 
     pub fn black_pieces(&self) -> u64 {
-        self.bitboards[BLACK_PIECES]
+        self.bitboards[Color::Black.bitboard()]
     }
 
-    pub fn print_board(&self) {
-        const PIECE_REP: [char; 6] = ['P', 'N', 'B', 'R', 'Q', 'K'];
-        let mut board: [char; 64] = ['.'; 64];
-        // Generate the visible board
-        for (piece_type, bitboard) in self.bitboards.iter().enumerate() {
-            if piece_type == 6 || piece_type == 7 { continue; }
-
-            for square in 0..64 {
-                if (bitboard & (1u64 << square)) != 0 {
-                    board[square] = if self.bitboards[WHITE_PIECES] & (1u64 << square) != 0 {
-                        PIECE_REP[piece_type]
-                    } else {
-                        PIECE_REP[piece_type].to_lowercase().next().unwrap()
-                    };
+    pub fn colored(&self, color: Color) -> u64 {
+        self.bitboards[color.bitboard()]
+    }
+
+    pub fn pieces(&self, kind: PieceKind) -> u64 {
+        self.bitboards[kind.index()]
+    }
+
+    pub fn piece_bitboard(&self, piece: Piece) -> u64 {
+        self.bitboards[piece.kind.index()] & self.bitboards[piece.color.bitboard()]
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        if self.all_pieces() & square.bit() == 0 {
+            return None;
+        }
+        let color = if self.white_pieces() & square.bit() != 0 { Color::White } else { Color::Black };
+        PieceKind::ALL.into_iter()
+            .find(|kind| self.bitboards[kind.index()] & square.bit() != 0)
+            .map(|kind| Piece::new(color, kind))
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        let kings = self.pieces(PieceKind::King) & self.colored(color);
+        if kings == 0 { None } else { Some(Square::from_index(kings.trailing_zeros() as usize)) }
+    }
+
+    // Pieces of both colors attacking square, sliders see through anything left out of occupancy
+    pub fn attackers_to(&self, square: Square, occupancy: u64) -> u64 {
+        let bishops = self.pieces(PieceKind::Bishop) | self.pieces(PieceKind::Queen);
+        let rooks = self.pieces(PieceKind::Rook) | self.pieces(PieceKind::Queen);
+
+        (pawn_attacks(Color::White, square) & self.piece_bitboard(Piece::new(Color::Black, PieceKind::Pawn))) |
+            (pawn_attacks(Color::Black, square) & self.piece_bitboard(Piece::new(Color::White, PieceKind::Pawn))) |
+            (knight_attacks(square) & self.pieces(PieceKind::Knight)) |
+            (king_attacks(square) & self.pieces(PieceKind::King)) |
+            (magic::bishop_attacks(square, occupancy) & bishops) |
+            (magic::rook_attacks(square, occupancy) & rooks)
+    }
+
+    // Every square a piece of color attacks
+    pub fn attacks(&self, color: Color) -> u64 {
+        let occupancy = self.all_pieces();
+        let mut attacks = 0;
+        for square in squares(self.colored(color)) {
+            attacks |= match self.piece_at(square).unwrap().kind {
+                PieceKind::Pawn => pawn_attacks(color, square),
+                PieceKind::Knight => knight_attacks(square),
+                PieceKind::Bishop => magic::bishop_attacks(square, occupancy),
+                PieceKind::Rook => magic::rook_attacks(square, occupancy),
+                PieceKind::Queen => magic::queen_attacks(square, occupancy),
+                PieceKind::King => king_attacks(square),
+            };
+        }
+
+        attacks
+    }
+
+    // Enemy pieces giving check to color's king
+    pub fn checkers(&self, color: Color) -> u64 {
+        self.king_square(color).map_or(0, |king| self.attackers_to(king, self.all_pieces()) & self.colored(!color))
+    }
+
+    // Pieces of either color that are the only thing between color's king and an enemy slider
+    pub fn blockers_for_king(&self, color: Color) -> u64 {
+        let Some(king) = self.king_square(color) else {
+            return 0;
+        };
+        // Sliders that would see the king on an empty board
+        let snipers = ((magic::bishop_attacks(king, 0) & (self.pieces(PieceKind::Bishop) | self.pieces(PieceKind::Queen))) |
+            (magic::rook_attacks(king, 0) & (self.pieces(PieceKind::Rook) | self.pieces(PieceKind::Queen)))) & self.colored(!color);
+
+        let mut blockers = 0;
+        for sniper in squares(snipers) {
+            let between = between(king, sniper) & self.all_pieces();
+            if between.count_ones() == 1 {
+                blockers |= between;
+            }
+        }
+
+        blockers
+    }
+
+    // Pieces of color that can't leave the line to their king
+    pub fn pinned(&self, color: Color) -> u64 {
+        self.blockers_for_king(color) & self.colored(color)
+    }
+
+    pub fn put_piece(&mut self, piece: Piece, square: Square) {
+        self.bitboards[piece.kind.index()] |= square.bit();
+        self.bitboards[piece.color.bitboard()] |= square.bit();
+        self.hash ^= piece_key(piece, square);
+    }
+
+    pub fn remove_piece(&mut self, piece: Piece, square: Square) {
+        self.bitboards[piece.kind.index()] &= !square.bit();
+        self.bitboards[piece.color.bitboard()] &= !square.bit();
+        self.hash ^= piece_key(piece, square);
+    }
+
+    // Only the piece placement field, Position::to_fen adds the rest
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(Square::from_index(rank * 8 + file)) {
+                    Some(piece) => {
+                        if empty != 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.to_char());
+                    }
+                    None => empty += 1,
                 }
             }
+            if empty != 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank != 0 {
+                fen.push('/');
+            }
         }
 
+        fen
+    }
+
+    pub fn print_board(&self) {
         // Print the board
         println!("  A B C D E F G H");
         for i in 0..8 {
             print!("{}", 8 - i);
             for j in 0..8 {
-                print!(" {}", board[(7 - i) * 8 + j]);
+                print!(" {}", self.piece_at(Square::from_index((7 - i) * 8 + j)).map_or('.', |piece| piece.to_char()));
             }
             println!();
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::board::{bits, create_default_board, square_from_algebraic};
+    use crate::position::create_position_from_fen;
+    use crate::types::{squares, Color};
+
+    #[test]
+    fn test_attackers_to() {
+        let board = create_position_from_fen("4k3/8/8/3r4/4P3/2N5/8/3QK2B w - - 0 1").board;
+        let sq = square_from_algebraic;
+        assert_eq!(board.attackers_to(sq("d5"), board.all_pieces()), bits(&["e4", "c3", "d1"]));
+        assert_eq!(board.attackers_to(sq("e2"), board.all_pieces()), bits(&["c3", "d1", "e1"]));
+        // the queen behind the rook only counts once the rook is gone
+        assert_eq!(board.attackers_to(sq("d8"), board.all_pieces()), bits(&["d5", "e8"]));
+        assert_eq!(board.attackers_to(sq("d8"), board.all_pieces() & !sq("d5").bit()), bits(&["d5", "d1", "e8"]));
+    }
+
+    #[test]
+    fn test_attack_maps() {
+        let board = create_default_board();
+        assert_eq!(board.attacks(Color::White), 0xFF_FF_7E);
+        assert_eq!(board.attacks(Color::Black), 0x7E_FF_FF << 40);
+        assert_eq!(board.checkers(Color::White), 0);
+
+        let position = create_position_from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert_eq!(position.checkers(), bits(&["h4"]));
+        assert!(squares(position.board.attacks(Color::Black)).any(|square| square == position.board.king_square(Color::White).unwrap()));
+    }
+
+    #[test]
+    fn test_pins_and_blockers() {
+        // A black piece in the way still blocks, but isn't pinned to the white king
+        let board = create_position_from_fen("4k3/8/8/8/1b6/8/3p4/4K3 w - - 0 1").board;
+        assert_eq!(board.pinned(Color::White), 0);
+        assert_eq!(board.blockers_for_king(Color::White), bits(&["d2"]));
+
+        let board = create_position_from_fen("4k3/4r3/8/8/8/8/4N3/1b2K3 w - - 0 1").board;
+        assert_eq!(board.pinned(Color::White), bits(&["e2"]));
+        assert_eq!(board.blockers_for_king(Color::White), bits(&["e2"]));
+        assert_eq!(board.pinned(Color::Black), 0);
+
+        // Two pieces in the way means neither is pinned
+        let board = create_position_from_fen("4k3/4r3/8/8/4P3/8/4N3/4K3 w - - 0 1").board;
+        assert_eq!(board.blockers_for_king(Color::White), 0);
+    }
+}
+
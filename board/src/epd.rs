@@ -0,0 +1,340 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::moves::Move;
+use crate::position::{try_create_position_from_fen, Position};
+use crate::Error;
+
+// One EPD line, the position plus whichever operations it had
+#[derive(Clone)]
+pub struct Epd {
+    pub position: Position,
+    pub best_moves: Vec<Move>, // bm
+    pub avoid_moves: Vec<Move>, // am
+    pub id: Option<String>,
+    pub comments: [Option<String>; 10], // c0 to c9
+    pub depth: Option<u32>, // acd
+    pub nodes: Option<u64>, // acn
+    pub centipawns: Option<i32>, // ce
+    pub pv: Vec<Move>,
+    pub perft: Vec<(u32, u64)>, // D1 to Dn, (depth, nodes)
+    pub halfmove_clock: Option<u32>, // hmvc
+    pub fullmove_number: Option<u32>, // fmvn
+    pub other: Vec<(String, Vec<String>)>, // opcodes not listed above, kept as written
+}
+
+impl Epd {
+    pub fn new(position: Position) -> Epd {
+        Epd {
+            position,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+            id: None,
+            comments: Default::default(),
+            depth: None,
+            nodes: None,
+            centipawns: None,
+            pv: Vec::new(),
+            perft: Vec::new(),
+            halfmove_clock: None,
+            fullmove_number: None,
+            other: Vec::new(),
+        }
+    }
+
+    // Expected node count for the depth, from the D operations
+    pub fn perft_nodes(&self, depth: u32) -> Option<u64> {
+        self.perft.iter().find(|&&(d, _)| d == depth).map(|&(_, nodes)| nodes)
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidEpd(message)
+}
+
+fn parse_number<T: FromStr>(opcode: &str, operands: &[String]) -> Result<T, Error> {
+    match operands {
+        [operand] => operand.parse().map_err(|_| invalid(format!("bad value \"{}\" for {}", operand, opcode))),
+        _ => Err(invalid(format!("{} takes one value but has {}", opcode, operands.len()))),
+    }
+}
+
+fn parse_string(opcode: &str, operands: &[String]) -> Result<String, Error> {
+    match operands {
+        [operand] => Ok(operand.clone()),
+        _ => Err(invalid(format!("{} takes one string but has {}", opcode, operands.len()))),
+    }
+}
+
+// Moves are SAN, but UCI moves are taken too
+fn parse_move(position: &Position, text: &str) -> Result<Move, Error> {
+    position.parse_san(text)
+        .or_else(|err| position.parse_uci_move(text).map_err(|_| err))
+        .map_err(|err| invalid(err.to_string()))
+}
+
+// Splits the operations into opcodes and operands, quoted strings can hold spaces and semicolons
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, Error> {
+    let mut operations = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ';' {
+            chars.next();
+            if !current.is_empty() {
+                let opcode = current.remove(0);
+                operations.push((opcode, std::mem::take(&mut current)));
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => string.push(c),
+                    None => return Err(invalid("unterminated string".to_string())),
+                }
+            }
+            if current.is_empty() {
+                return Err(invalid(format!("string \"{}\" without an opcode", string)));
+            }
+            current.push(string);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|&&c| !c.is_whitespace() && c != ';') {
+                word.push(c);
+                chars.next();
+            }
+            current.push(word);
+        }
+    }
+
+    // Perft suites often leave the last ; off
+    if !current.is_empty() {
+        let opcode = current.remove(0);
+        operations.push((opcode, current));
+    }
+    Ok(operations)
+}
+
+impl FromStr for Epd {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Epd, Error> {
+        // Four fen fields, everything after them is operations
+        let mut fen = Vec::new();
+        let mut rest = line.trim_start();
+        while fen.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fen.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if fen.len() != 4 {
+            return Err(Error::InvalidFieldCount(fen.len()));
+        }
+
+        // Perft suites often keep the two fen clocks, opcodes never start with a digit
+        while fen.len() < 6 {
+            let end = rest.find(|c: char| c.is_whitespace() || c == ';').unwrap_or(rest.len());
+            if end == 0 || !rest[..end].bytes().all(|c| c.is_ascii_digit()) {
+                break;
+            }
+            fen.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+
+        let mut epd = Epd::new(try_create_position_from_fen(&fen.join(" "))?);
+        // Kept as hmvc and fmvn so they're written back out
+        if fen.len() > 4 {
+            epd.halfmove_clock = Some(epd.position.halfmove_clock);
+        }
+        if fen.len() > 5 {
+            epd.fullmove_number = Some(epd.position.fullmove_number);
+        }
+        let operations = parse_operations(rest)?;
+
+        // The clocks go in first so the moves are read from the right position
+        for (opcode, operands) in &operations {
+            match opcode.as_str() {
+                "hmvc" => {
+                    let clock = parse_number(opcode, operands)?;
+                    epd.halfmove_clock = Some(clock);
+                    epd.position.halfmove_clock = clock;
+                }
+                "fmvn" => {
+                    let number = parse_number(opcode, operands)?;
+                    epd.fullmove_number = Some(number);
+                    epd.position.fullmove_number = number;
+                }
+                _ => {}
+            }
+        }
+
+        for (opcode, operands) in operations {
+            match opcode.as_str() {
+                "hmvc" | "fmvn" => {}
+                "bm" | "am" => {
+                    let moves = operands.iter().map(|text| parse_move(&epd.position, text)).collect::<Result<Vec<_>, _>>()?;
+                    if opcode == "bm" { epd.best_moves = moves; } else { epd.avoid_moves = moves; }
+                }
+                "id" => epd.id = Some(parse_string(&opcode, &operands)?),
+                "acd" => epd.depth = Some(parse_number(&opcode, &operands)?),
+                "acn" => epd.nodes = Some(parse_number(&opcode, &operands)?),
+                "ce" => epd.centipawns = Some(parse_number(&opcode, &operands)?),
+                "pv" => {
+                    let mut position = epd.position.clone();
+                    for text in &operands {
+                        let move_ = parse_move(&position, text)?;
+                        epd.pv.push(move_);
+                        position.push(move_);
+                    }
+                }
+                _ => {
+                    let comment = opcode.strip_prefix('c').and_then(|n| n.parse::<usize>().ok()).filter(|&n| n < 10 && opcode.len() == 2);
+                    let perft = opcode.strip_prefix('D').and_then(|n| n.parse::<u32>().ok()).filter(|&n| n > 0);
+                    if let Some(n) = comment {
+                        epd.comments[n] = Some(parse_string(&opcode, &operands)?);
+                    } else if let Some(depth) = perft {
+                        epd.perft.push((depth, parse_number(&opcode, &operands)?));
+                    } else {
+                        epd.other.push((opcode, operands));
+                    }
+                }
+            }
+        }
+
+        Ok(epd)
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text)
+}
+
+impl fmt::Display for Epd {
+    // The four fen fields then the operations in a fixed order
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fen = self.position.to_fen();
+        write!(f, "{}", fen.split(' ').take(4).collect::<Vec<_>>().join(" "))?;
+
+        let mut operations: Vec<(String, Vec<String>)> = Vec::new();
+        let san = |moves: &[Move]| moves.iter().map(|&move_| self.position.san(move_)).collect::<Vec<_>>();
+        if !self.best_moves.is_empty() {
+            operations.push(("bm".to_string(), san(&self.best_moves)));
+        }
+        if !self.avoid_moves.is_empty() {
+            operations.push(("am".to_string(), san(&self.avoid_moves)));
+        }
+        if let Some(id) = &self.id {
+            operations.push(("id".to_string(), vec![quote(id)]));
+        }
+        for (n, comment) in self.comments.iter().enumerate() {
+            if let Some(comment) = comment {
+                operations.push((format!("c{}", n), vec![quote(comment)]));
+            }
+        }
+        if let Some(depth) = self.depth {
+            operations.push(("acd".to_string(), vec![depth.to_string()]));
+        }
+        if let Some(nodes) = self.nodes {
+            operations.push(("acn".to_string(), vec![nodes.to_string()]));
+        }
+        if let Some(centipawns) = self.centipawns {
+            operations.push(("ce".to_string(), vec![centipawns.to_string()]));
+        }
+        if !self.pv.is_empty() {
+            let mut position = self.position.clone();
+            let mut moves = Vec::new();
+            for &move_ in &self.pv {
+                moves.push(position.san(move_));
+                position.push(move_);
+            }
+            operations.push(("pv".to_string(), moves));
+        }
+        for (depth, nodes) in &self.perft {
+            operations.push((format!("D{}", depth), vec![nodes.to_string()]));
+        }
+        if let Some(clock) = self.halfmove_clock {
+            operations.push(("hmvc".to_string(), vec![clock.to_string()]));
+        }
+        if let Some(number) = self.fullmove_number {
+            operations.push(("fmvn".to_string(), vec![number.to_string()]));
+        }
+        for (opcode, operands) in &self.other {
+            let operands = operands.iter()
+                .map(|operand| if operand.contains([' ', ';']) || operand.is_empty() { quote(operand) } else { operand.clone() })
+                .collect();
+            operations.push((opcode.clone(), operands));
+        }
+
+        for (opcode, operands) in operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                write!(f, " {}", operand)?;
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::epd::Epd;
+    use crate::Error;
+
+    #[test]
+    fn test_parse_epd() {
+        let epd: Epd = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 d2d4; am f3; id \"start; test\"; \
+            c0 \"a comment\"; c9 \"last\"; acd 12; acn 123456; ce -25; pv e4 e5 Nf3; hmvc 3; fmvn 7; xyz 1 \"two words\";"
+            .parse().unwrap();
+        assert_eq!(epd.best_moves.iter().map(|move_| move_.to_uci()).collect::<Vec<_>>(), ["e2e4", "d2d4"]);
+        assert_eq!(epd.avoid_moves.len(), 1);
+        assert_eq!(epd.id.as_deref(), Some("start; test"));
+        assert_eq!(epd.comments[0].as_deref(), Some("a comment"));
+        assert_eq!(epd.comments[9].as_deref(), Some("last"));
+        assert_eq!((epd.depth, epd.nodes, epd.centipawns), (Some(12), Some(123456), Some(-25)));
+        assert_eq!(epd.pv.iter().map(|move_| move_.to_uci()).collect::<Vec<_>>(), ["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(epd.position.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3 7");
+        assert_eq!(epd.other, vec![("xyz".to_string(), vec!["1".to_string(), "two words".to_string()])]);
+
+        assert_eq!(epd.to_string(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 d4; am f3; id \"start; test\"; \
+            c0 \"a comment\"; c9 \"last\"; acd 12; acn 123456; ce -25; pv e4 e5 Nf3; hmvc 3; fmvn 7; xyz 1 \"two words\";");
+    }
+
+    #[test]
+    fn test_perft_epd() {
+        // The layout perft suites use
+        let epd: Epd = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - D1 48 ;D2 2039 ;D3 97862".parse().unwrap();
+        assert_eq!(epd.perft, vec![(1, 48), (2, 2039), (3, 97862)]);
+        assert_eq!(epd.perft_nodes(2), Some(2039));
+        assert_eq!(epd.perft_nodes(4), None);
+        assert_eq!(epd.to_string(), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - D1 48; D2 2039; D3 97862;");
+        assert_eq!(epd.to_string().parse::<Epd>().unwrap().perft, epd.perft);
+
+        let epd: Epd = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 3 40 ;D1 14 ;D2 191".parse().unwrap();
+        assert_eq!(epd.perft, vec![(1, 14), (2, 191)]);
+        assert_eq!((epd.position.halfmove_clock, epd.position.fullmove_number), (3, 40));
+        assert_eq!((epd.halfmove_clock, epd.fullmove_number), (Some(3), Some(40)));
+        assert!(epd.other.is_empty());
+        assert_eq!(epd.to_string(), "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - D1 14; D2 191; hmvc 3; fmvn 40;");
+        let again: Epd = epd.to_string().parse().unwrap();
+        assert_eq!((again.position.to_fen(), again.perft), (epd.position.to_fen(), epd.perft));
+    }
+
+    #[test]
+    fn test_epd_errors() {
+        for line in [
+            "8/8/8/8 w",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - acd x;",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e5;",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"open;",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - acd 1 2;",
+        ] {
+            assert!(line.parse::<Epd>().is_err(), "{}", line);
+        }
+        assert_eq!("8/8/8/8 w".parse::<Epd>().err(), Some(Error::InvalidFieldCount(2)));
+    }
+}
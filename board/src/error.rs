@@ -0,0 +1,58 @@
+use std::fmt;
+use crate::types::{Color, Square};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    InvalidSquare(String),
+    InvalidRankCount(usize),
+    InvalidPiece(char),
+    RankOverfull(u32), // rank number as written on the board, 1-8
+    RankIncomplete(u32),
+    InvalidFieldCount(usize),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    InvalidKingCount(Color, u32),
+    OpponentInCheck,
+    EmptySquare(Square),
+    InvalidUciMove(String),
+    IllegalMove(String),
+    InvalidSan(String),
+    AmbiguousMove(String),
+    InvalidPgn { game: usize, ply: usize, message: String }, // game counts from 1, ply 0 is before the moves
+    InvalidEpd(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSquare(square) => write!(f, "Invalid square \"{}\"", square),
+            Error::InvalidRankCount(count) => write!(f, "Invalid fen format, expected 8 ranks but found {}", count),
+            Error::InvalidPiece(piece) => write!(f, "Invalid fen format, unknown piece '{}'", piece),
+            Error::RankOverfull(rank) => write!(f, "Invalid fen format, rank {} has more than 8 squares", rank),
+            Error::RankIncomplete(rank) => write!(f, "Invalid fen format, rank {} has fewer than 8 squares", rank),
+            Error::InvalidFieldCount(count) => write!(f, "Invalid fen format, expected 4 to 6 fields but found {}", count),
+            Error::InvalidSideToMove(side) => write!(f, "Invalid side to move \"{}\"", side),
+            Error::InvalidCastling(castling) => write!(f, "Invalid castling rights \"{}\"", castling),
+            Error::InvalidEnPassant(square) => write!(f, "Invalid en passant square \"{}\"", square),
+            Error::InvalidHalfmoveClock(clock) => write!(f, "Invalid halfmove clock \"{}\"", clock),
+            Error::InvalidFullmoveNumber(number) => write!(f, "Invalid fullmove number \"{}\"", number),
+            Error::InvalidKingCount(color, count) => {
+                let side = if *color == Color::White { "white" } else { "black" };
+                write!(f, "Invalid position, expected one {} king but found {}", side, count)
+            }
+            Error::OpponentInCheck => write!(f, "Invalid position, the side not to move is in check"),
+            Error::EmptySquare(square) => write!(f, "No piece on the given square {}", square),
+            Error::InvalidUciMove(text) => write!(f, "Invalid uci move \"{}\"", text),
+            Error::IllegalMove(text) => write!(f, "Illegal move \"{}\"", text),
+            Error::InvalidSan(text) => write!(f, "Invalid san move \"{}\"", text),
+            Error::InvalidPgn { game, ply, message } => write!(f, "Invalid pgn in game {} at ply {}, {}", game, ply, message),
+            Error::InvalidEpd(message) => write!(f, "Invalid epd, {}", message),
+            Error::AmbiguousMove(text) => write!(f, "Ambiguous move \"{}\", more than one piece can make it", text),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
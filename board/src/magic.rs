@@ -0,0 +1,240 @@
+use std::sync::OnceLock;
+use crate::moves::{ray_bishop_attacks, ray_rook_attacks};
+use crate::types::Square;
+
+const RANK_1: u64 = 0xFF;
+const RANK_8: u64 = 0xFF << 56;
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = FILE_A << 7;
+
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    mask: u64, // squares whose occupancy changes the attacks, board edges left out
+    magic: u64,
+    shift: u32,
+    offset: usize, // start of this square's attacks in the shared table
+}
+
+struct SliderTable {
+    magics: [Magic; 64],
+    attacks: Vec<u64>,
+}
+
+struct MagicTables {
+    rook: SliderTable,
+    bishop: SliderTable,
+    pext: bool,
+}
+
+static TABLES: OnceLock<MagicTables> = OnceLock::new();
+
+fn tables() -> &'static MagicTables {
+    TABLES.get_or_init(|| MagicTables::new(bmi2_supported()))
+}
+
+pub fn rook_attacks(square: Square, occupancy: u64) -> u64 {
+    tables().rook_attacks(square, occupancy)
+}
+
+pub fn bishop_attacks(square: Square, occupancy: u64) -> u64 {
+    tables().bishop_attacks(square, occupancy)
+}
+
+pub fn queen_attacks(square: Square, occupancy: u64) -> u64 {
+    rook_attacks(square, occupancy) | bishop_attacks(square, occupancy)
+}
+
+// Whether the lookups index with the BMI2 pext instruction instead of a magic multiply
+pub fn uses_pext() -> bool {
+    tables().pext
+}
+
+#[cfg(target_arch = "x86_64")]
+fn bmi2_supported() -> bool {
+    is_x86_feature_detected!("bmi2")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn bmi2_supported() -> bool {
+    false
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "bmi2")]
+fn pext(value: u64, mask: u64) -> u64 {
+    std::arch::x86_64::_pext_u64(value, mask)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn pext(_value: u64, _mask: u64) -> u64 {
+    unreachable!("pext is only used when bmi2 is detected");
+}
+
+// xorshift64*, fixed seed so the same magics are found on every run
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Magics with few bits set are found much faster
+    fn sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
+
+impl MagicTables {
+    // Falls back to magics when asked for pext on a cpu without bmi2
+    fn new(pext: bool) -> MagicTables {
+        let pext = pext && bmi2_supported();
+        let mut random = Random(0x9E37_79B9_7F4A_7C15);
+        MagicTables {
+            rook: SliderTable::new(rook_mask, ray_rook_attacks, pext, &mut random),
+            bishop: SliderTable::new(bishop_mask, ray_bishop_attacks, pext, &mut random),
+            pext,
+        }
+    }
+
+    fn rook_attacks(&self, square: Square, occupancy: u64) -> u64 {
+        self.rook.attacks(square, occupancy, self.pext)
+    }
+
+    fn bishop_attacks(&self, square: Square, occupancy: u64) -> u64 {
+        self.bishop.attacks(square, occupancy, self.pext)
+    }
+}
+
+fn rook_mask(square: Square) -> u64 {
+    let edges = ((RANK_1 | RANK_8) & !(RANK_1 << (8 * square.rank().index()))) |
+        ((FILE_A | FILE_H) & !(FILE_A << square.file().index()));
+    ray_rook_attacks(0, square) & !edges
+}
+
+fn bishop_mask(square: Square) -> u64 {
+    ray_bishop_attacks(0, square) & !(RANK_1 | RANK_8 | FILE_A | FILE_H)
+}
+
+impl SliderTable {
+    fn new(mask_for: fn(Square) -> u64, slow_attacks: fn(u64, Square) -> u64, pext_index: bool, random: &mut Random) -> SliderTable {
+        let mut magics = [Magic::default(); 64];
+        let mut attacks = Vec::new();
+
+        for (index, magic) in magics.iter_mut().enumerate() {
+            let square = Square::from_index(index);
+            let mask = mask_for(square);
+            let bits = mask.count_ones();
+
+            // Every subset of the mask together with the attacks it gives
+            let mut occupancies = Vec::with_capacity(1 << bits);
+            let mut subset: u64 = 0;
+            loop {
+                occupancies.push((subset, slow_attacks(subset, square)));
+                subset = subset.wrapping_sub(mask) & mask;
+                if subset == 0 { break; }
+            }
+
+            magic.mask = mask;
+            magic.shift = 64 - bits;
+            magic.offset = attacks.len();
+            attacks.resize(attacks.len() + occupancies.len(), 0);
+            let table = &mut attacks[magic.offset..];
+
+            if pext_index {
+                for &(occupancy, attack) in &occupancies {
+                    // only reached when bmi2 was detected
+                    table[unsafe { pext(occupancy, mask) } as usize] = attack;
+                }
+            } else {
+                magic.magic = find_magic(mask, magic.shift, &occupancies, table, random);
+            }
+        }
+
+        SliderTable { magics, attacks }
+    }
+
+    fn attacks(&self, square: Square, occupancy: u64, pext_index: bool) -> u64 {
+        let magic = &self.magics[square.index()];
+        let index = if pext_index {
+            // only set when bmi2 was detected
+            unsafe { pext(occupancy, magic.mask) as usize }
+        } else {
+            ((occupancy & magic.mask).wrapping_mul(magic.magic) >> magic.shift) as usize
+        };
+        self.attacks[magic.offset + index]
+    }
+}
+
+// Tries random magics until every occupancy lands on a slot holding its own attacks, fills table on the way
+fn find_magic(mask: u64, shift: u32, occupancies: &[(u64, u64)], table: &mut [u64], random: &mut Random) -> u64 {
+    let mut used = vec![0u32; occupancies.len()]; // attempt that last wrote each slot, saves clearing the table
+    let mut attempt = 0;
+    loop {
+        let magic = random.sparse();
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue; // not enough high bits to spread the index
+        }
+
+        attempt += 1;
+        let found = occupancies.iter().all(|&(occupancy, attack)| {
+            let index = (occupancy.wrapping_mul(magic) >> shift) as usize;
+            if used[index] != attempt {
+                used[index] = attempt;
+                table[index] = attack;
+                true
+            } else {
+                table[index] == attack // a collision is fine when the attacks are the same
+            }
+        });
+
+        if found {
+            return magic;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::magic::{bmi2_supported, MagicTables, Random};
+    use crate::moves::{ray_bishop_attacks, ray_rook_attacks};
+    use crate::types::Square;
+
+    fn verify(tables: &MagicTables) {
+        let mut random = Random(0x1234_5678_9ABC_DEF1);
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            for _ in 0..2000 {
+                // Mix of sparse and dense boards
+                let occupancy = if random.next() & 1 == 0 { random.sparse() } else { random.next() };
+                assert_eq!(tables.rook_attacks(square, occupancy), ray_rook_attacks(occupancy, square), "rook on {} with {:x}", square, occupancy);
+                assert_eq!(tables.bishop_attacks(square, occupancy), ray_bishop_attacks(occupancy, square), "bishop on {} with {:x}", square, occupancy);
+            }
+            assert_eq!(tables.rook_attacks(square, 0), ray_rook_attacks(0, square));
+            assert_eq!(tables.rook_attacks(square, !0), ray_rook_attacks(!0, square));
+            assert_eq!(tables.bishop_attacks(square, 0), ray_bishop_attacks(0, square));
+            assert_eq!(tables.bishop_attacks(square, !0), ray_bishop_attacks(!0, square));
+        }
+    }
+
+    #[test]
+    fn test_magic_attacks() {
+        verify(&MagicTables::new(false));
+    }
+
+    #[test]
+    fn test_pext_attacks() {
+        if bmi2_supported() {
+            verify(&MagicTables::new(true));
+        }
+    }
+
+    #[test]
+    fn test_table_sizes() {
+        let tables = MagicTables::new(false);
+        assert_eq!(tables.rook.attacks.len(), 102_400);
+        assert_eq!(tables.bishop.attacks.len(), 5_248);
+    }
+}
@@ -1,1421 +1,1002 @@
-use crate::board::{Board, square_to_algebraic}; // , square_to_algebraic;
-use crate::board::{BISHOP, ROOK, QUEEN};
+use crate::board::Board;
+use crate::magic;
+use crate::tables::{between, king_attacks, knight_attacks, line, pawn_attacks};
+use crate::types::{squares, CastleRights, Color, Piece, PieceKind, Promotion, Rank, Square};
+use crate::Error;
 // use crate::board::print_bitboard;
 
-pub const CASTLE_WHITE_KING_SIDE: u32 = 0b0001;
-pub const CASTLE_WHITE_QUEEN_SIDE: u32 = 0b0010;
-pub const CASTLE_BLACK_QUEEN_SIDE: u32 = 0b0100;
-pub const CASTLE_BLACK_KING_SIDE: u32 = 0b1000;
+pub const WHITE_KING_SQUARE: Square = Square::E1;
+pub const BLACK_KING_SQUARE: Square = Square::E8;
 
-pub const WHITE_KING_SQUARE: u64 = 4;
-pub const BLACK_KING_SQUARE: u64 = 60;
+// (files, ranks) steps used to walk rays across the board
+const NORTH: (i32, i32) = (0, 1);
+const SOUTH: (i32, i32) = (0, -1);
+const EAST: (i32, i32) = (1, 0);
+const WEST: (i32, i32) = (-1, 0);
+const NORTH_EAST: (i32, i32) = (1, 1);
+const NORTH_WEST: (i32, i32) = (-1, 1);
+const SOUTH_EAST: (i32, i32) = (1, -1);
+const SOUTH_WEST: (i32, i32) = (-1, -1);
 
-#[derive(Clone, Copy)]
-pub struct Move {
-    pub from: u64,
-    pub to: u64,
-    pub promotion_type: char,
-    pub capture: char,
-    pub en_passant: i32,
-    pub castle_rights: u32,
+// from in bits 0-5, to in bits 6-11 and the flags in bits 12-15
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Move(u16);
+
+// Everything make_move changes that can't be worked out from the move itself
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Undo {
+    pub capture: Option<PieceKind>,
+    pub castle_rights: CastleRights,
+    pub en_passant: Option<Square>,
+}
+
+// The castling right lost when anything moves from or to the square
+fn corner_castle_right(square: Square) -> CastleRights {
+    match square {
+        Square::A1 => CastleRights::WHITE_QUEEN_SIDE,
+        Square::H1 => CastleRights::WHITE_KING_SIDE,
+        Square::A8 => CastleRights::BLACK_QUEEN_SIDE,
+        Square::H8 => CastleRights::BLACK_KING_SIDE,
+        _ => CastleRights::NONE,
+    }
 }
 
 impl Move {
-    pub fn is_castle(&self) -> bool {
-        (self.from == 60 && (self.to == 62 || self.to == 58)) ||
-            (self.from == 4 && (self.to == 6 || self.to == 2))
-    }
-
-    fn get_castle_rook_pos(&self) -> (u64, u64, usize) {
-        let rook_pos: u64;
-        let rook_n_pos: u64;
-        let color: usize;
-        if self.from == 60 && self.to == 62 {
-            rook_pos = 63;
-            rook_n_pos = 61;
-            color = 7;
-        } else if self.from == 60 && self.to == 58 {
-            rook_pos = 56;
-            rook_n_pos = 59;
-            color = 7;
-        } else if self.from == 4 && self.to == 6 {
-            rook_pos = 7;
-            rook_n_pos = 5;
-            color = 6;
-        } else {
-            rook_pos = 0;
-            rook_n_pos = 3;
-            color = 6;
-        }
+    pub const QUIET: u16 = 0;
+    pub const DOUBLE_PAWN_PUSH: u16 = 1;
+    pub const KING_CASTLE: u16 = 2;
+    pub const QUEEN_CASTLE: u16 = 3;
+    pub const CAPTURE: u16 = 4;
+    pub const EN_PASSANT: u16 = 5;
+    pub const PROMOTION: u16 = 8; // the low two bits hold the Promotion index
 
-        (rook_pos, rook_n_pos, color)
+    pub const fn new(from: Square, to: Square, flags: u16) -> Move {
+        Move(from.index() as u16 | (to.index() as u16) << 6 | (flags & 0xF) << 12)
     }
 
-    pub fn make_move(&self, board: &mut Board) -> (u64, u32) { // returns an en passant square and castle rights
-        // println!("Before move {}{}={}", square_to_algebraic(self.from), square_to_algebraic(self.to), self.promotion_type);
-        // board.print_board();
-        let piece_type = find_piece_type(board, self.from);
-        let piece_bitboard = get_bitboard_val(piece_type);
-        let mut castle_rights = self.castle_rights;
+    pub const fn new_promotion(from: Square, to: Square, promotion: Promotion, capture: bool) -> Move {
+        Move::new(from, to, Move::PROMOTION | if capture { Move::CAPTURE } else { 0 } | promotion.index() as u16)
+    }
 
-        board.bitboards[piece_bitboard] &= !(1u64 << self.from); // clear the piece from the old square
-        board.bitboards[piece_bitboard] |= 1u64 << self.to; // set the piece to the new square
+    pub const fn from_bits(bits: u16) -> Move {
+        Move(bits)
+    }
 
-        let capture_bitboard = match self.capture {
-            'p' | 'P' => 0,
-            'n' | 'N' => 1,
-            'b' | 'B' => 2,
-            'r' | 'R' => 3,
-            'q' | 'Q' => 4,
-            'k' | 'K' => 5,
-            _ => 6,
-        };
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
 
-        if capture_bitboard != 6 {
-            if capture_bitboard != piece_bitboard {
-                board.bitboards[capture_bitboard] &= !(1u64 << self.to);
-            }
+    pub const fn from(self) -> Square {
+        Square::from_index((self.0 & 0x3F) as usize)
+    }
 
-            if self.capture.is_uppercase() {
-                board.bitboards[6] &= !(1u64 << self.to);
-            } else {
-                board.bitboards[7] &= !(1u64 << self.to);
-            }
-        }
+    pub const fn to(self) -> Square {
+        Square::from_index((self.0 >> 6 & 0x3F) as usize)
+    }
 
-        if self.promotion_type != 'z' {
-            board.bitboards[match self.promotion_type {
-                'n' => 1,
-                'b' => 2,
-                'r' => 3,
-                'q' => 4,
-                _ => { unreachable!("Invalid promotion type {}", self.promotion_type); }
-            }] |= 1u64 << self.to; // set the promoted piece
+    pub const fn flags(self) -> u16 {
+        self.0 >> 12
+    }
 
-            board.bitboards[0] &= !(1u64 << self.to); // clear the promoted pawn
+    pub const fn promotion(self) -> Option<Promotion> {
+        if self.flags() & Move::PROMOTION != 0 {
+            Some(Promotion::from_index(self.flags() as usize))
+        } else {
+            None
         }
+    }
 
-        if (piece_type == 'k' || piece_type == 'K') && self.is_castle() {
-            let (rook_pos, rook_n_pos, color) = self.get_castle_rook_pos();
+    pub const fn is_capture(self) -> bool {
+        self.flags() & Move::CAPTURE != 0
+    }
 
-            board.bitboards[color] |= 1u64 << rook_n_pos; // Set the rook
-            board.bitboards[color] &= !(1u64 << rook_pos); // Remove the rook
-            board.bitboards[ROOK] |= 1u64 << rook_n_pos; // Set the rook
-            board.bitboards[ROOK] &= !(1u64 << rook_pos); // Remove the rook
+    pub const fn is_en_passant(self) -> bool {
+        self.flags() == Move::EN_PASSANT
+    }
 
-            if piece_type.is_uppercase() { // clear the castling rights for that side
-                castle_rights &= !(CASTLE_WHITE_QUEEN_SIDE | CASTLE_WHITE_KING_SIDE);
-            } else {
-                castle_rights &= !(CASTLE_BLACK_QUEEN_SIDE | CASTLE_BLACK_KING_SIDE);
-            }
-        }
-        if piece_type == 'K' {
-            castle_rights &= !(CASTLE_WHITE_KING_SIDE | CASTLE_WHITE_QUEEN_SIDE);
-        }
-        if piece_type == 'k' {
-            castle_rights &= !(CASTLE_BLACK_QUEEN_SIDE | CASTLE_BLACK_KING_SIDE);
-        }
-        if piece_type == 'R' || piece_type == 'r' {
-            if self.from == 0 { castle_rights &= !(CASTLE_WHITE_QUEEN_SIDE) }
-            if self.from == 7 { castle_rights &= !(CASTLE_WHITE_KING_SIDE) }
-            if self.from == 56 { castle_rights &= !(CASTLE_BLACK_QUEEN_SIDE) }
-            if self.from == 63 { castle_rights &= !(CASTLE_BLACK_KING_SIDE) }
-        }
+    pub const fn is_double_pawn_push(self) -> bool {
+        self.flags() == Move::DOUBLE_PAWN_PUSH
+    }
 
-        if self.en_passant != 0 {
-            if piece_type.is_uppercase() {
-                let clear = !(1u64 << (4 * 8 + (self.from as i32 % 8) - self.en_passant));
-                board.bitboards[7] &= clear; // clear the taken pawn
-                board.bitboards[0] &= clear;
-            } else {
-                let clear = !(1u64 << (3 * 8 + (self.from as i32 % 8) - self.en_passant));
-                board.bitboards[6] &= clear;
-                board.bitboards[0] &= clear;
-            }
-        }
+    pub const fn is_castle(self) -> bool {
+        self.flags() == Move::KING_CASTLE || self.flags() == Move::QUEEN_CASTLE
+    }
 
-        // Set the color bitboards
-        if piece_type.is_uppercase() {
-            board.bitboards[6] &= !(1u64 << self.from);
-            board.bitboards[6] |= 1u64 << self.to;
-        } else {
-            board.bitboards[7] &= !(1u64 << self.from);
-            board.bitboards[7] |= 1u64 << self.to;
+    // Long algebraic form, like e2e4 or e7e8q
+    pub fn to_uci(self) -> String {
+        match self.promotion() {
+            Some(promotion) => format!("{}{}{}", self.from(), self.to(), promotion.to_char()),
+            None => format!("{}{}", self.from(), self.to()),
         }
+    }
 
-        // board.print_board();
-        // println!("Move made");
-
-        (if piece_type == 'p' {
-            if self.from / 8 == 6 && self.to / 8 == 4 {
-                self.from - 8
-            } else {
-                65
-            }
-        } else if piece_type == 'P' {
-            if self.from / 8 == 1 && self.to / 8 == 3 {
-                self.from + 8
-            } else {
-                65
-            }
-        } else {
-            65
-        }, castle_rights)
+    fn get_castle_rook_pos(self) -> (Square, Square) {
+        match self.to() {
+            Square::G8 => (Square::H8, Square::F8),
+            Square::C8 => (Square::A8, Square::D8),
+            Square::G1 => (Square::H1, Square::F1),
+            _ => (Square::A1, Square::D1),
+        }
     }
 
-    pub fn unmake_move(&self, board: &mut Board) {
-        let mut piece_type = find_piece_type(board, self.to);
-        let mut piece_bitboard = get_bitboard_val(piece_type);
-        // println!("Unmaking move");
-        // board.print_board();
+    // The pawn taken en passant stands next to the moving pawn
+    fn en_passant_square(self) -> Square {
+        Square::new(self.to().file(), self.from().rank())
+    }
 
-        if (piece_type == 'k' || piece_type == 'K') && self.is_castle() {
-            let (rook_pos, rook_n_pos, color) = self.get_castle_rook_pos();
+    // Updates the castle rights and en passant square in place, the returned Undo puts them back
+    pub fn make_move(self, board: &mut Board, castle_rights: &mut CastleRights, en_passant: &mut Option<Square>) -> Undo {
+        let (from, to) = (self.from(), self.to());
+        let piece = find_piece_type(board, from);
+        let undo = Undo {
+            capture: if self.is_capture() && !self.is_en_passant() { board.piece_at(to).map(|piece| piece.kind) } else { None },
+            castle_rights: *castle_rights,
+            en_passant: *en_passant,
+        };
 
-            board.bitboards[ROOK] |= 1u64 << rook_pos; // Set the rook
-            board.bitboards[color] |= 1u64 << rook_pos; // Set the rook
-            board.bitboards[color] &= !(1u64 << rook_n_pos); // Remove the rook
-            board.bitboards[ROOK] &= !(1u64 << rook_n_pos); // Remove the rook
+        if let Some(capture) = undo.capture {
+            board.remove_piece(Piece::new(!piece.color, capture), to);
         }
+        board.remove_piece(piece, from); // clear the piece from the old square
+        board.put_piece(match self.promotion() {
+            Some(promotion) => Piece::new(piece.color, promotion.kind()),
+            None => piece,
+        }, to); // set the piece (or the promoted piece) on the new square
 
-        if self.promotion_type != 'z' {
-            board.bitboards[piece_bitboard] &= !(1u64 << self.to); // clear the promoted piece
-
-            piece_type = if piece_type.is_uppercase() { 'P' } else { 'p' };
-            piece_bitboard = 0;
+        if self.is_en_passant() {
+            board.remove_piece(Piece::new(!piece.color, PieceKind::Pawn), self.en_passant_square()); // clear the taken pawn
         }
 
-
-        if self.en_passant != 0 {
-            if piece_type.is_uppercase() {
-                let set = 1u64 << (4 * 8 + (self.from as i32 % 8) - self.en_passant);
-                board.bitboards[7] |= set; // Add the taken pawn back in
-                board.bitboards[0] |= set;
-            } else {
-                let set = 1u64 << (3 * 8 + (self.from as i32 % 8) - self.en_passant);
-                board.bitboards[6] |= set;
-                board.bitboards[0] |= set;
+        if piece.kind == PieceKind::King {
+            if self.is_castle() {
+                let (rook_pos, rook_n_pos) = self.get_castle_rook_pos();
+                let rook = Piece::new(piece.color, PieceKind::Rook);
+                board.remove_piece(rook, rook_pos);
+                board.put_piece(rook, rook_n_pos);
             }
+            castle_rights.remove(CastleRights::for_color(piece.color));
         }
+        // a rook leaving or taken on its starting square can't castle anymore
+        castle_rights.remove(corner_castle_right(from) | corner_castle_right(to));
 
-        board.bitboards[piece_bitboard] &= !(1u64 << self.to);
-        board.bitboards[piece_bitboard] |= 1u64 << self.from;
-
-        if piece_type.is_uppercase() {
-            board.bitboards[6] &= !(1u64 << self.to);
-            board.bitboards[6] |= 1u64 << self.from;
+        *en_passant = if self.is_double_pawn_push() {
+            Some(Square::from_index((from.index() + to.index()) / 2))
         } else {
-            board.bitboards[7] &= !(1u64 << self.to);
-            board.bitboards[7] |= 1u64 << self.from;
-        }
+            None
+        };
 
-        if self.capture != 'z' {
-            board.bitboards[get_bitboard_val(self.capture)] |= 1u64 << self.to; // set the captured piece
-            if self.capture.is_uppercase() {
-                board.bitboards[6] |= 1u64 << self.to;
-            } else {
-                board.bitboards[7] |= 1u64 << self.to;
-            }
-        }
-        // println!("After move {}{}", square_to_algebraic(self.from), square_to_algebraic(self.to));
-        // board.print_board();
+        undo
     }
-}
 
-#[derive(Clone, Copy)]
-struct GeneratorBoard<'a> {
-    pub board: &'a Board,
-    pub white_attacks: u64,
-    pub black_attacks: u64,
-    pub white_king: u64,
-    pub black_king: u64,
-}
+    pub fn unmake_move(self, board: &mut Board, castle_rights: &mut CastleRights, en_passant: &mut Option<Square>, undo: Undo) {
+        let (from, to) = (self.from(), self.to());
+        let moved = find_piece_type(board, to);
+        let piece = if self.promotion().is_some() { Piece::new(moved.color, PieceKind::Pawn) } else { moved };
 
-fn get_bitboard_val(piece_type: char) -> usize {
-    match piece_type {
-        'p' | 'P' => 0,
-        'n' | 'N' => 1,
-        'b' | 'B' => 2,
-        'r' | 'R' => 3,
-        'q' | 'Q' => 4,
-        'k' | 'K' => 5,
-        _ => { unreachable!("Invalid board"); }
-    }
-}
-
-fn find_king(board: &Board, white: bool) -> u64 {
-    let mut index = 0;
-    for square in 0..64 {
-        if board.bitboards[5] & (1u64 << square) != 0 {
-            if white && (board.white_pieces() & (1u64 << square)) != 0 {
-                index = square;
-                break;
-            }
-            if !white && (board.black_pieces() & (1u64 << square)) != 0 {
-                index = square;
-                break;
-            }
+        if piece.kind == PieceKind::King && self.is_castle() {
+            let (rook_pos, rook_n_pos) = self.get_castle_rook_pos();
+            let rook = Piece::new(piece.color, PieceKind::Rook);
+            board.remove_piece(rook, rook_n_pos);
+            board.put_piece(rook, rook_pos);
         }
-    }
-
-    index
-}
-
-fn get_attacks(board: &Board) -> GeneratorBoard {
-    let mut att_board = GeneratorBoard {
-        board,
-        white_attacks: 0,
-        black_attacks: 0,
-        white_king: 0,
-        black_king: 0,
-    };
-    let all_pieces = board.all_pieces();
 
-    for square in 0..64 {
-        if all_pieces & (1u64 << square) == 0 { continue; }
-        for piece in 0..6 {
-            if board.bitboards[piece] & (1u64 << square) != 0 {
-                let val = match piece {
-                    0 => get_pawn_attacks(board, square),
-                    1 => get_knight_attacks(square),
-                    2 => get_bishop_attacks(board.all_pieces(), square),
-                    3 => get_rook_attacks(board.all_pieces(), square),
-                    4 => get_queen_attacks(board.all_pieces(), square),
-                    5 => get_king_attacks(square),
-                    _ => { unreachable!("Invalid board"); }
-                };
-
-                if board.white_pieces() & (1u64 << square) != 0 {
-                    att_board.white_attacks |= val;
-                } else {
-                    att_board.black_attacks |= val;
-                }
+        board.remove_piece(moved, to);
+        board.put_piece(piece, from);
 
-                break; // Piece found, no need to check other types
-            }
+        if let Some(capture) = undo.capture {
+            board.put_piece(Piece::new(!piece.color, capture), to); // set the captured piece
         }
-    }
-
-    att_board
-}
-
-pub fn get_piece_moves(board: &Board, square: u64, en_passant: u64) -> u64 {
-    get_piece_moves_wa(&get_attacks(board), square, en_passant)
-}
-
-fn get_piece_moves_wa(attacks: &GeneratorBoard, square: u64, en_passant: u64) -> u64 {
-    for (i, bitboard) in attacks.board.bitboards.iter().enumerate() {
-        if i == 6 || i == 7 { continue; }
-
-        if bitboard & (1u64 << square) != 0 {
-            return match i {
-                0 => get_pawn_moves(*attacks, square, en_passant),
-                1 => get_knight_moves(*attacks, square),
-                2 => get_bishop_moves(*attacks, square),
-                3 => get_rook_moves(*attacks, square),
-                4 => get_queen_moves(*attacks, square),
-                5 => get_king_moves(*attacks, square),
-                _ => { unreachable!("Invalid board"); }
-            };
+        if self.is_en_passant() {
+            board.put_piece(Piece::new(!piece.color, PieceKind::Pawn), self.en_passant_square()); // Add the taken pawn back in
         }
+
+        *castle_rights = undo.castle_rights;
+        *en_passant = undo.en_passant;
     }
-    // attacks.board.print_board();
-    // print_bitboard(attacks.board.white_pieces(), 'W', '.');
-    // print_bitboard(attacks.board.black_pieces(), 'B', '.');
-    panic!("No piece on the given square {}", square_to_algebraic(square));
 }
 
-pub fn find_piece_type(board: &Board, square: u64) -> char {
-    for piece in 0..6 {
-        if board.bitboards[piece] & (1u64 << square) != 0 {
-            return if board.white_pieces() & (1u64 << square) != 0 {
-                match piece {
-                    0 => 'P',
-                    1 => 'N',
-                    2 => 'B',
-                    3 => 'R',
-                    4 => 'Q',
-                    5 => 'K',
-                    _ => { unreachable!("Invalid board"); }
-                }
-            } else {
-                match piece {
-                    0 => 'p',
-                    1 => 'n',
-                    2 => 'b',
-                    3 => 'r',
-                    4 => 'q',
-                    5 => 'k',
-                    _ => { unreachable!("Invalid board"); }
-                }
-            };
-        }
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_uci())
     }
-    panic!("No piece on the given square");
 }
 
-fn count_check(board: &GeneratorBoard, white: bool, en_passant: u64) -> (bool, u64) {
-    let mut possible_blocks: u64 = 0;
-    let mut count: u64 = 0;
-    for square in 0..64 {
-        if (if white { board.board.black_pieces() } else { board.board.white_pieces() } & (1u64 << square)) == 0 { continue; }
-        for piece in 0..6 {
-            if board.board.bitboards[piece] & (1u64 << square) != 0 {
-                let val = match piece {
-                    0 => get_pawn_attacks(board.board, square),
-                    1 => get_knight_attacks(square),
-                    2 => get_bishop_attacks(board.board.all_pieces(), square),
-                    3 => get_rook_attacks(board.board.all_pieces(), square),
-                    4 => get_queen_attacks(board.board.all_pieces(), square),
-                    5 => get_king_attacks(square),
-                    _ => { unreachable!("Invalid board"); }
-                };
-
-                if ((1u64 << if white { board.white_king } else { board.black_king }) & val) != 0 {
-                    if count == 1 {
-                        return (true, 0); // double check
-                    } else {
-                        count = 1;
-
-                        // Allow en passant capture
-                        if piece == 0 && en_passant > 15 && en_passant < 48 {
-                            if white {
-                                if en_passant == square + 8 {
-                                    possible_blocks |= 1u64 << en_passant; // allow en passant
-                                }
-                            } else if en_passant == square - 8 {
-                                possible_blocks |= 1u64 << en_passant;
-                            }
-                        }
-
-                        possible_blocks |= 1u64 << square; // allow capture of the piece
-                        possible_blocks |= match piece {
-                            0 => 0,
-                            1 => 0,
-                            2 => get_between(board, square, if white { board.white_king } else { board.black_king }),
-                            3 => get_between(board, square, if white { board.white_king } else { board.black_king }),
-                            4 => get_between(board, square, if white { board.white_king } else { board.black_king }),
-                            _ => { unreachable!("Invalid board"); }
-                        };
-                    }
-                }
-                break;
-            }
-        }
-    }
+// More than the most legal moves any position has
+pub const MAX_MOVES: usize = 256;
 
-    (false, possible_blocks)
+// Fixed size list on the stack, with a score next to each move for ordering
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    scores: [i32; MAX_MOVES],
+    len: usize,
 }
 
-pub fn in_check(board: &Board, white: bool) -> bool {
-    let king = if white { find_king(board, true) } else { find_king(board, false) };
-    for square in 0..64 {
-        if board.bitboards[if white { 6 } else { 7 }] & (1u64 << square) != 0 { continue; }
-        for piece in 0..6 {
-            if (board.bitboards[piece] & (1u64 << square)) != 0 &&
-                ((1u64 << king) & match piece {
-                    0 => get_pawn_attacks(board, square),
-                    1 => get_knight_attacks(square),
-                    2 => get_bishop_attacks(board.all_pieces(), square),
-                    3 => get_rook_attacks(board.all_pieces(), square),
-                    4 => get_queen_attacks(board.all_pieces(), square),
-                    5 => get_king_attacks(square),
-                    _ => { unreachable!("Invalid board"); }
-                }) != 0 {
-                return true;
-            }
-        }
+impl MoveList {
+    pub const fn new() -> MoveList {
+        MoveList { moves: [Move(0); MAX_MOVES], scores: [0; MAX_MOVES], len: 0 }
     }
 
-    false
-}
+    pub fn push(&mut self, move_: Move) {
+        self.moves[self.len] = move_;
+        self.scores[self.len] = 0;
+        self.len += 1;
+    }
 
-// fn check_move(board: &mut Board, move_: &Move, white: bool) -> bool {
-//     move_.make_move(board);
-//     let val = in_check(board, white);
-//     move_.unmake_move(board);
-//
-//     val
-// }
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-pub fn get_moves(board: &Board, en_passant: u64, castle_rights: u32, white: bool) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-    let mut att_board = get_attacks(board);
-    att_board.white_king = find_king(board, true);
-    att_board.black_king = find_king(board, false);
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
 
-    let my_pieces = if white { board.white_pieces() } else { board.black_pieces() };
-    let mut possible_squares = !0u64;
-    let mut double_check = false;
-    if (white && ((att_board.black_attacks & (1u64 << att_board.white_king)) != 0)) ||
-        (!white && ((att_board.white_attacks & (1u64 << att_board.black_king)) != 0)) {
-        // in check
-        (double_check, possible_squares) = count_check(&att_board, white, en_passant);
-        // board.print_board();
-        // board::print_bitboard(possible_squares, 'X', '.');
+    pub fn get(&self, index: usize) -> Option<Move> {
+        self.as_slice().get(index).copied()
     }
 
-    if double_check {
-        // Only king can move
-        let possible_moves = get_piece_moves_wa(&att_board, if white { att_board.white_king } else { att_board.black_king }, en_passant);
-        for pm_square in 0..64 {
-            if possible_moves & (1u64 << pm_square) != 0 {
-                let mut capture = 'z';
-                if board.all_pieces() & (1u64 << pm_square) != 0 {
-                    // find the piece that is being captured
-                    capture = find_piece_type(board, pm_square);
-                }
+    pub fn contains(&self, move_: Move) -> bool {
+        self.as_slice().contains(&move_)
+    }
 
-                moves.push(Move {
-                    from: att_board.white_king,
-                    to: pm_square,
-                    promotion_type: 'z',
-                    en_passant: 0,
-                    capture,
-                    castle_rights,
-                });
-            }
-        }
+    pub fn as_slice(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
 
-        return moves;
+    pub fn iter(&self) -> std::iter::Copied<std::slice::Iter<'_, Move>> {
+        self.as_slice().iter().copied()
     }
 
-    // Normal moves
-    for square in 0..64 {
-        if my_pieces & (1u64 << square) == 0 { continue; }
-        let possible_moves = get_piece_moves_wa(&att_board, square, en_passant) &
-            (if ((1u64 << square) & board.bitboards[5]) == 0 { possible_squares } else { !0u64 });
+    pub fn score(&self, index: usize) -> i32 {
+        self.scores[..self.len][index]
+    }
 
-        if possible_moves == 0 { continue; } // No moves, no reason to loop through them
-        for pm_square in 0..64 {
-            if possible_moves & (1u64 << pm_square) != 0 {
-                let move_piece_type = find_piece_type(board, square);
-                let mut capture = 'z';
-                if board.all_pieces() & (1u64 << pm_square) != 0 {
-                    // find the piece that is being captured
-                    capture = find_piece_type(board, pm_square);
-                }
+    pub fn set_score(&mut self, index: usize, score: i32) {
+        self.scores[..self.len][index] = score;
+    }
 
-                // Deal with promotion
-                if (move_piece_type == 'p' && pm_square / 8 == 0) || (move_piece_type == 'P' && pm_square / 8 == 7) {
-                    for promotion in ['q', 'r', 'b', 'n'] {
-                        moves.push(Move {
-                            from: square,
-                            to: pm_square,
-                            promotion_type: promotion,
-                            en_passant: 0,
-                            capture,
-                            castle_rights,
-                        });
-                        // if check_move(&mut check_board, &n_move, white) {
-                        //     moves.push(n_move);
-                        // }
-                    }
-                } else {
-                    moves.push(Move {
-                        from: square,
-                        to: pm_square,
-                        promotion_type: 'z',
-                        en_passant: is_en_passant(board, square, pm_square),
-                        capture,
-                        castle_rights,
-                    });
-                }
-            }
-        }
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.moves[..self.len].swap(a, b);
+        self.scores[..self.len].swap(a, b);
     }
 
-    fn check_castling(board: &Board, castle_rights: u32, pieces: u64, side: u32, king_square: i32, direction: i32, attacks: u64) -> bool {
-        let rook_square = if side == CASTLE_WHITE_KING_SIDE {
-            7
-        } else if side == CASTLE_WHITE_QUEEN_SIDE {
-            0
-        } else if side == CASTLE_BLACK_QUEEN_SIDE {
-            56
-        } else { 63 };
-
-        ((castle_rights & side) != 0) &&
-            ((board.bitboards[5] & (1u64 << king_square)) != 0) && // Ensure king is on it's starting square
-            ((board.bitboards[3] & (1u64 << rook_square)) != 0) && // Ensure king is on it's starting square
-            ((pieces & (1u64 << (king_square + direction))) == 0) && // No pieces in between
-            ((pieces & (1u64 << (king_square + direction * 2))) == 0) &&
-            // make sure QueenSide castling is possible
-            (if rook_square == 0 || rook_square == 56 { pieces & (1u64 << (king_square + direction * 3)) == 0 } else { true }) &&
-            ((attacks & (1u64 << king_square)) == 0) && // No checks in between
-            ((attacks & (1u64 << (king_square + direction))) == 0) &&
-            ((attacks & (1u64 << (king_square + direction * 2))) == 0) &&
-            (board.bitboards[3] & (1u64 << rook_square)) != 0 // Ensure rook is available to castle with
-    }
-
-    if castle_rights != 0 {
-        // Castling
-        if white {
-            if check_castling(board, castle_rights, board.all_pieces(), CASTLE_WHITE_KING_SIDE,
-                              WHITE_KING_SQUARE as i32, 1, att_board.black_attacks) {
-                moves.push(Move {
-                    from: WHITE_KING_SQUARE,
-                    to: WHITE_KING_SQUARE + 2,
-                    promotion_type: 'z',
-                    en_passant: 0,
-                    capture: 'z',
-                    castle_rights,
-                });
-            }
-            if check_castling(board, castle_rights, board.all_pieces(), CASTLE_WHITE_QUEEN_SIDE,
-                              WHITE_KING_SQUARE as i32, -1, att_board.black_attacks) {
-                moves.push(Move {
-                    from: WHITE_KING_SQUARE,
-                    to: WHITE_KING_SQUARE - 2,
-                    promotion_type: 'z',
-                    en_passant: 0,
-                    capture: 'z',
-                    castle_rights,
-                });
-            }
-        }
-        if !white {
-            if check_castling(board, castle_rights, board.all_pieces(), CASTLE_BLACK_KING_SIDE,
-                              BLACK_KING_SQUARE as i32, 1, att_board.white_attacks) {
-                moves.push(Move {
-                    from: BLACK_KING_SQUARE,
-                    to: BLACK_KING_SQUARE + 2,
-                    promotion_type: 'z',
-                    en_passant: 0,
-                    capture: 'z',
-                    castle_rights,
-                });
-            }
-            if check_castling(board, castle_rights, board.all_pieces(), CASTLE_BLACK_QUEEN_SIDE,
-                              BLACK_KING_SQUARE as i32, -1, att_board.white_attacks) {
-                moves.push(Move {
-                    from: BLACK_KING_SQUARE,
-                    to: BLACK_KING_SQUARE - 2,
-                    promotion_type: 'z',
-                    en_passant: 0,
-                    capture: 'z',
-                    castle_rights,
-                });
+    // Keeps the moves (and their scores) for which keep is true, in order
+    pub fn retain(&mut self, mut keep: impl FnMut(Move) -> bool) {
+        let mut kept = 0;
+        for index in 0..self.len {
+            if keep(self.moves[index]) {
+                self.moves[kept] = self.moves[index];
+                self.scores[kept] = self.scores[index];
+                kept += 1;
             }
         }
+        self.len = kept;
     }
 
-    moves
-}
-
-fn is_en_passant(board: &Board, from: u64, to: u64) -> i32 {
-    let piece_type = find_piece_type(board, from);
-    if piece_type == 'p' {
-        if from / 8 == 3 && to / 8 == 2 { // Capture
-            if ((1u64 << (3 * 8 + (to % 8))) & board.bitboards[0]) != 0 && // Pawn on same rank
-                ((1u64 << to) & board.all_pieces()) == 0 {
-                return (from % 8) as i32 - (to % 8) as i32;
+    // Highest score first, moves with equal scores keep their order
+    pub fn sort_by_score(&mut self) {
+        for i in 1..self.len {
+            let mut j = i;
+            while j > 0 && self.scores[j - 1] < self.scores[j] {
+                self.swap(j - 1, j);
+                j -= 1;
             }
         }
-    } else if piece_type == 'P' && from / 8 == 4 && to / 8 == 5 &&
-        ((1u64 << (4 * 8 + (to % 8))) & board.bitboards[0]) != 0 &&
-        ((1u64 << to) & board.all_pieces()) == 0 { // No pieces where taking
-        return (from % 8) as i32 - (to % 8) as i32;
     }
 
-    0
+    // Moves the best scored move from index onward to index, for picking moves one at a time
+    pub fn pick_best(&mut self, index: usize) -> Move {
+        let best = (index..self.len).fold(index, |best, i| if self.scores[i] > self.scores[best] { i } else { best });
+        self.swap(index, best);
+        self.moves[..self.len][index]
+    }
 }
 
-fn piece_on_top_left(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while (index % 8 > 0) && (index / 8 < 8) && (index < 57) {
-        index += 7;
-
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
-        }
+impl Default for MoveList {
+    fn default() -> MoveList {
+        MoveList::new()
     }
+}
 
-    false
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
 }
 
-fn is_king_top_left(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king = if white { board.white_king } else { board.black_king } as i64;
+impl std::ops::Index<usize> for MoveList {
+    type Output = Move;
 
-    let i_square = square as i64;
-    if (i_square / 8 < king / 8 && i_square % 8 > king % 8) &&
-        (king / 8 - i_square / 8 == i_square % 8 - king % 8) {
-        piece_on_top_left(board, square, 1u64 << king)
-    } else {
-        false
+    fn index(&self, index: usize) -> &Move {
+        &self.as_slice()[index]
     }
 }
 
-fn piece_on_top_right(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while (index % 8 < 7) && (index / 8 < 7) && (index < 55) {
-        index += 9;
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = Move;
+    type IntoIter = std::iter::Copied<std::slice::Iter<'a, Move>>;
 
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
-        }
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
+}
 
-    false
+#[derive(Clone, Copy)]
+struct GeneratorBoard<'a> {
+    pub board: &'a Board,
+    pub attacks: [u64; 2], // seen through the other side's king, so it can't step back along a check
+    pub kings: [Square; 2],
+    pub pinned: [u64; 2],
 }
 
-fn is_king_top_right(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king = if white { board.white_king } else { board.black_king } as i64;
-    let i_square = square as i64;
-    if (i_square / 8 < king / 8 && i_square % 8 < king % 8) &&
-        (king / 8 - i_square / 8 == king % 8 - i_square % 8) {
-        piece_on_top_right(board, square, 1u64 << king)
-    } else {
-        false
+impl GeneratorBoard<'_> {
+    fn king(&self, color: Color) -> Square {
+        self.kings[color.index()]
     }
-}
 
-fn piece_on_bottom_left(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while (index % 8 > 0) && (index / 8 > 0) && (index > 8) {
-        index -= 9;
+    fn attacks_by(&self, color: Color) -> u64 {
+        self.attacks[color.index()]
+    }
 
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
+    // Where the piece on square may go without exposing its king
+    fn pin_mask(&self, square: Square) -> u64 {
+        let color = color_on(self.board, square);
+        if self.pinned[color.index()] & square.bit() != 0 {
+            line(self.king(color), square)
+        } else {
+            !0
         }
     }
-
-    false
 }
 
-fn is_king_bottom_left(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king = if white { board.white_king } else { board.black_king } as i64;
-
-    let i_square = square as i64;
-    if (i_square / 8 > king / 8 && i_square % 8 > king % 8) &&
-        (i_square / 8 - king / 8 == i_square % 8 - king % 8) {
-        piece_on_bottom_left(board, square, 1u64 << king)
-    } else {
-        false
-    }
+fn find_king(board: &Board, color: Color) -> Square {
+    board.king_square(color).unwrap_or(Square::A1)
 }
 
-fn piece_on_bottom_right(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while (index % 8 < 7) && (index / 8 > 0) && (index > 6) {
-        index -= 7;
+fn get_attacks(board: &Board) -> GeneratorBoard<'_> {
+    let kings = [find_king(board, Color::White), find_king(board, Color::Black)];
+    let mut att_board = GeneratorBoard {
+        board,
+        attacks: [0; 2],
+        kings,
+        pinned: [board.pinned(Color::White), board.pinned(Color::Black)],
+    };
 
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
-        }
+    for square in squares(board.all_pieces()) {
+        let piece = find_piece_type(board, square);
+        let occupancy = board.all_pieces() & !att_board.king(!piece.color).bit();
+        att_board.attacks[piece.color.index()] |= get_piece_attacks(occupancy, piece, square);
     }
 
-    false
+    att_board
 }
 
-fn is_king_bottom_right(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king: i64 = (if white { board.white_king } else { board.black_king }) as i64;
-
-    let i_square = square as i64;
-    if (i_square / 8 > king / 8 && i_square % 8 < king % 8) &&
-        (i_square / 8 - king / 8 == king % 8 - i_square % 8) {
-        piece_on_bottom_right(board, square, 1u64 << king)
-    } else {
-        false
+fn get_piece_attacks(occupancy: u64, piece: Piece, square: Square) -> u64 {
+    match piece.kind {
+        PieceKind::Pawn => pawn_attacks(piece.color, square),
+        PieceKind::Knight => knight_attacks(square),
+        PieceKind::Bishop => get_bishop_attacks(occupancy, square),
+        PieceKind::Rook => get_rook_attacks(occupancy, square),
+        PieceKind::Queen => get_queen_attacks(occupancy, square),
+        PieceKind::King => king_attacks(square),
     }
 }
 
-fn piece_on_left(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while index % 8 < 7 {
-        index += 1;
+pub fn get_piece_moves(board: &Board, square: Square, en_passant: Option<Square>) -> u64 {
+    get_piece_moves_wa(&get_attacks(board), square, en_passant)
+}
 
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
-        }
+fn get_piece_moves_wa(attacks: &GeneratorBoard, square: Square, en_passant: Option<Square>) -> u64 {
+    match find_piece_type(attacks.board, square).kind {
+        PieceKind::Pawn => get_pawn_moves(*attacks, square, en_passant),
+        PieceKind::Knight => get_knight_moves(*attacks, square),
+        PieceKind::Bishop => get_bishop_moves(*attacks, square),
+        PieceKind::Rook => get_rook_moves(*attacks, square),
+        PieceKind::Queen => get_queen_moves(*attacks, square),
+        PieceKind::King => get_king_moves(*attacks, square),
     }
+}
 
-    false
+pub fn find_piece_type(board: &Board, square: Square) -> Piece {
+    try_find_piece_type(board, square).unwrap_or_else(|err| panic!("{}", err))
 }
 
-fn is_king_left(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king = if white { board.white_king } else { board.black_king };
+pub fn try_find_piece_type(board: &Board, square: Square) -> Result<Piece, Error> {
+    board.piece_at(square).ok_or(Error::EmptySquare(square))
+}
 
-    if square / 8 == king / 8 && square % 8 < king % 8 {
-        piece_on_left(board, square, 1u64 << king)
-    } else {
-        false
+// Whether it's double check, otherwise the squares that capture or block the checker,
+// and separately the en passant square if only a pawn can resolve the check by taking there
+fn count_check(board: &GeneratorBoard, color: Color, en_passant: Option<Square>) -> (bool, u64, u64) {
+    let king = board.king(color);
+    let checkers = board.board.checkers(color);
+    if checkers.count_ones() > 1 {
+        return (true, 0, 0); // double check
     }
-}
 
-fn piece_on_right(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while index % 8 > 0 {
-        index -= 1;
+    let checker = Square::from_index(checkers.trailing_zeros() as usize);
+    let possible_blocks = checkers | between(king, checker);
 
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
+    // Allow en passant capture of a checking pawn
+    let mut passant_block = 0;
+    if let Some(en_passant) = en_passant {
+        if checkers & board.board.pieces(PieceKind::Pawn) != 0 &&
+            checker.offset(0, if color == Color::White { 1 } else { -1 }) == Some(en_passant) {
+            passant_block = en_passant.bit();
         }
     }
 
-    false
+    (false, possible_blocks, passant_block)
 }
 
-fn is_king_right(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king = if white { board.white_king } else { board.black_king };
-
-    if square / 8 == king / 8 && square % 8 > king % 8 {
-        piece_on_right(board, square, 1u64 << king)
-    } else {
-        false
-    }
+pub fn in_check(board: &Board, color: Color) -> bool {
+    board.checkers(color) != 0
 }
 
-fn piece_on_top(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while index / 8 < 7 {
-        index += 8;
-
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
-        }
-    }
+// fn check_move(board: &mut Board, move_: &Move, white: bool) -> bool {
+//     move_.make_move(board);
+//     let val = in_check(board, white);
+//     move_.unmake_move(board);
+//
+//     val
+// }
 
-    false
+// Which legal moves the generator produces
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GenType {
+    All,
+    Captures, // captures and every promotion, for quiescence
+    Quiets, // everything Captures leaves out, castling included
+    Evasions, // every move when in check, nothing otherwise
+    QuietChecks, // quiet moves that give check
 }
 
-fn is_king_top(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king = if white { board.white_king } else { board.black_king };
-
-    if square % 8 == king % 8 && square / 8 < king / 8 {
-        piece_on_top(board, square, 1u64 << king)
-    } else {
-        false
-    }
+// Whether color playing the move leaves the other side in check
+pub fn gives_check(board: &Board, move_: Move, color: Color) -> bool {
+    let mut board = board.clone();
+    move_.make_move(&mut board, &mut CastleRights::default(), &mut None);
+    in_check(&board, !color)
 }
 
-fn piece_on_bottom(board: &GeneratorBoard, square: u64, target_bitboard: u64) -> bool {
-    let mut index = square;
-    while index / 8 > 0 {
-        index -= 8;
-
-        if (board.board.all_pieces() & (1u64 << index)) != 0 {
-            return target_bitboard & (1u64 << index) != 0;
-        }
-    }
+pub fn get_moves(board: &Board, en_passant: Option<Square>, castle_rights: CastleRights, color: Color) -> Vec<Move> {
+    let mut moves = MoveList::new();
+    get_moves_into(board, en_passant, castle_rights, color, &mut moves);
+    moves.as_slice().to_vec()
+}
 
-    false
+// Same as get_moves without allocating, the list is cleared first
+pub fn get_moves_into(board: &Board, en_passant: Option<Square>, castle_rights: CastleRights, color: Color, moves: &mut MoveList) {
+    get_moves_of_type_into(board, en_passant, castle_rights, color, GenType::All, moves);
 }
 
-fn is_king_bottom(board: &GeneratorBoard, square: u64, white: bool) -> bool {
-    let king = if white { board.white_king } else { board.black_king };
+// Only the legal moves belonging to gen_type, the list is cleared first
+pub fn get_moves_of_type_into(board: &Board, en_passant: Option<Square>, castle_rights: CastleRights, color: Color, gen_type: GenType, moves: &mut MoveList) {
+    moves.clear();
 
-    if square % 8 == king % 8 && square / 8 > king / 8 {
-        piece_on_bottom(board, square, 1u64 << king)
-    } else {
-        false
+    let att_board = get_attacks(board);
+    let king = att_board.king(color);
+    let checked = in_check(board, color);
+    if gen_type == GenType::Evasions && !checked {
+        return;
     }
-}
 
-fn top_left_bottom_right(board: &Board, square: u64) -> u64 {
-    let mut moves: u64 = 0;
-    let mut loc: u64 = square;
-    let my_pieces = if board.white_pieces() & (1u64 << square) != 0 {
-        board.white_pieces()
-    } else {
-        board.black_pieces()
+    // Squares each piece may go to for this type, pawns also promote and take en passant
+    let enemies = board.colored(!color);
+    let passant = en_passant.map_or(0, |square| square.bit());
+    let last_rank = 0xFFu64 << (8 * Rank::Eighth.relative(color).index());
+    let (targets, pawn_targets) = match gen_type {
+        GenType::All | GenType::Evasions => (!0u64, !0u64),
+        GenType::Captures => (enemies, enemies | passant | last_rank),
+        GenType::Quiets | GenType::QuietChecks => (!enemies, !enemies & !passant & !last_rank),
     };
 
-    while loc / 8 > 0 && loc % 8 < 7 && loc > 6 { // Bottom Right
-        loc -= 7;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
-
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
-    }
-
-    loc = square;
-    while loc / 8 < 7 && loc % 8 > 0 && loc < 58 { // Top Left
-        loc += 7;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
+    // Quiet checks only go where they check the enemy king, or uncover a check by leaving its line
+    let (check_squares, discoverers) = match (gen_type, board.king_square(!color)) {
+        (GenType::QuietChecks, Some(enemy_king)) => (check_squares(board, enemy_king), board.blockers_for_king(!color) & board.colored(color)),
+        (GenType::QuietChecks, None) => ([0; 6], 0),
+        _ => ([!0; 6], 0),
+    };
+    let check_targets = |square: Square, kind: PieceKind| {
+        let discovered = match board.king_square(!color) {
+            Some(enemy_king) if discoverers & square.bit() != 0 => !line(enemy_king, square),
+            _ => 0,
+        };
+        check_squares[kind.index()] | discovered
+    };
 
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
+    let my_pieces = board.colored(color);
+    let mut possible_squares = !0u64;
+    let mut passant_block = 0u64;
+    let mut double_check = false;
+    if checked {
+        (double_check, possible_squares, passant_block) = count_check(&att_board, color, en_passant);
     }
 
-    moves
-}
-
-fn top_right_bottom_left(board: &Board, square: u64) -> u64 {
-    let mut moves: u64 = 0;
-    let mut loc: u64 = square;
-    let my_pieces = if board.white_pieces() & (1u64 << square) != 0 {
-        board.white_pieces()
+    if double_check {
+        // Only king can move
+        add_piece_moves(board, king, get_piece_moves_wa(&att_board, king, en_passant) & targets & check_targets(king, PieceKind::King), moves);
     } else {
-        board.black_pieces()
-    };
-
-    while loc / 8 < 7 && loc % 8 < 7 && loc < 56 { // Top Right
-        loc += 9;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
+        // Normal moves
+        for square in squares(my_pieces) {
+            let kind = find_piece_type(board, square).kind;
+            let possible_moves = get_piece_moves_wa(&att_board, square, en_passant) & check_targets(square, kind) & match kind {
+                PieceKind::King => targets,
+                PieceKind::Pawn => (possible_squares | passant_block) & pawn_targets,
+                _ => possible_squares & targets,
+            };
+            add_piece_moves(board, square, possible_moves, moves);
+        }
 
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
+        if !checked && matches!(gen_type, GenType::All | GenType::Quiets | GenType::QuietChecks) {
+            add_castling(board, castle_rights, color, att_board.attacks_by(!color), moves);
+        }
     }
 
-    loc = square;
-    while loc / 8 > 0 && loc % 8 > 0 && loc > 8 { // Bottom Left
-        loc -= 9;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
-
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
+    // Which castle checks depends on where the rook lands, and there are at most two of them
+    if gen_type == GenType::QuietChecks {
+        moves.retain(|move_| !move_.is_castle() || gives_check(board, move_, color));
     }
-
-    moves
 }
 
-fn all_bishop_moves(board: GeneratorBoard, square: u64) -> u64 {
-    top_left_bottom_right(board.board, square) | top_right_bottom_left(board.board, square)
+// The squares each kind of piece would check the king on from, indexed by PieceKind
+fn check_squares(board: &Board, king: Square) -> [u64; 6] {
+    let occupancy = board.all_pieces();
+    let color = find_piece_type(board, king).color;
+    [
+        pawn_attacks(color, king),
+        knight_attacks(king),
+        get_bishop_attacks(occupancy, king),
+        get_rook_attacks(occupancy, king),
+        get_queen_attacks(occupancy, king),
+        0,
+    ]
 }
 
-fn top_bottom(board: &Board, square: u64) -> u64 {
-    let mut moves: u64 = 0;
-    let mut loc: u64 = square;
-    let my_pieces = if board.white_pieces() & (1u64 << square) != 0 {
-        board.white_pieces()
-    } else {
-        board.black_pieces()
-    };
-
-    while loc < 56 { // Top
-        loc += 8;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
-
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
+// Every move of color that follows the piece movement rules, own king safety is left to is_legal
+pub fn get_pseudo_legal_moves_into(board: &Board, en_passant: Option<Square>, castle_rights: CastleRights, color: Color, moves: &mut MoveList) {
+    moves.clear();
+    for square in squares(board.colored(color)) {
+        add_piece_moves(board, square, pseudo_legal_targets(board, en_passant, square), moves);
     }
-
-    loc = square;
-    while loc > 7 { // Bottom
-        loc -= 8;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
-
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
+    if !castle_rights.is_empty() {
+        add_castling(board, castle_rights, color, get_attacks(board).attacks_by(!color), moves);
     }
-
-    moves
 }
 
-fn left_right(board: &Board, square: u64) -> u64 {
-    let mut moves: u64 = 0;
-    let mut loc: u64 = square;
-    let my_pieces = if board.white_pieces() & (1u64 << square) != 0 {
-        board.white_pieces()
-    } else {
-        board.black_pieces()
+// Whether move_ is one color could make here ignoring pins and checks, without generating every move
+pub fn is_pseudo_legal(board: &Board, en_passant: Option<Square>, castle_rights: CastleRights, color: Color, move_: Move) -> bool {
+    let Some(piece) = board.piece_at(move_.from()) else {
+        return false;
     };
-
-    while loc % 8 > 0 { // Right
-        loc -= 1;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
-
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
+    if piece.color != color {
+        return false;
     }
 
-    loc = square;
-    while loc % 8 < 7 { // Left
-        loc += 1;
-        moves |= if my_pieces & (1u64 << loc) == 0 { 1u64 << loc } else { 0 };
-
-        if board.all_pieces() & (1u64 << loc) != 0 { break; }
+    let mut moves = MoveList::new();
+    if move_.is_castle() {
+        add_castling(board, castle_rights, color, get_attacks(board).attacks_by(!color), &mut moves);
+    } else if pseudo_legal_targets(board, en_passant, move_.from()) & move_.to().bit() != 0 {
+        add_piece_moves(board, move_.from(), move_.to().bit(), &mut moves);
     }
 
-    moves
+    moves.contains(move_)
 }
 
-fn all_rook_moves(board: GeneratorBoard, square: u64) -> u64 {
-    left_right(board.board, square) | top_bottom(board.board, square)
+// Whether a pseudo legal move leaves color's own king safe
+pub fn is_legal(board: &Board, color: Color, move_: Move) -> bool {
+    let mut board = board.clone();
+    move_.make_move(&mut board, &mut CastleRights::default(), &mut None);
+    !in_check(&board, color)
 }
 
-fn check_en_passant(board: &GeneratorBoard, square: u64, en_passant: u64) -> u64 {
-    if en_passant > 63 || en_passant == 0 {
-        return 0;
-    }
-    let mut board_cp: Board = board.board.clone();
-    let white = board.board.white_pieces() & (1u64 << square) != 0;
-
-    let clear = !(1u64 << (en_passant as i32 + if white { -8 } else { 8 }));
-    board_cp.bitboards[if white { 7 } else { 6 }] &= clear; // clear the taken pawn
-    board_cp.bitboards[0] &= clear;
-
-    board_cp.bitboards[if white { 6 } else { 7 }] |= 1u64 << en_passant; // set the new square
-    board_cp.bitboards[0] |= 1u64 << en_passant;
-    // println!("{}", square_to_algebraic(square));
-    board_cp.bitboards[if white { 6 } else { 7 }] &= !(1u64 << square); // clear the old square
-    board_cp.bitboards[0] &= !(1u64 << square);
-
-    for square in 0..64 {
-        for piece in 2..5 {
-            if board_cp.bitboards[if white { 6 } else { 7 }] & (1u64 << square) != 0 { continue; }
-            if (board_cp.bitboards[piece] & (1u64 << square)) != 0 &&
-                ((1u64 << (if white { board.white_king } else { board.black_king })) & match piece {
-                    2 => get_bishop_attacks(board_cp.all_pieces(), square),
-                    3 => get_rook_attacks(board_cp.all_pieces(), square),
-                    4 => get_queen_attacks(board_cp.all_pieces(), square),
-                    _ => { unreachable!("Invalid board"); }
-                }) != 0 {
-                return 0;
-            }
+fn pseudo_legal_targets(board: &Board, en_passant: Option<Square>, square: Square) -> u64 {
+    let piece = find_piece_type(board, square);
+    let occupancy = board.all_pieces();
+    // The king is never taken, the move before would have been illegal
+    let blocked = board.colored(piece.color) | board.pieces(PieceKind::King);
+
+    match piece.kind {
+        PieceKind::Pawn => {
+            let passant = match en_passant {
+                Some(en_passant) if (2..=5).contains(&square.rank().index()) => en_passant.bit(),
+                _ => 0,
+            };
+            (pawn_attacks(piece.color, square) & (board.colored(!piece.color) & !blocked | passant)) |
+                pawn_pushes(board, piece.color, square)
         }
+        PieceKind::Knight => knight_attacks(square) & !blocked,
+        PieceKind::Bishop => get_bishop_attacks(occupancy, square) & !blocked,
+        PieceKind::Rook => get_rook_attacks(occupancy, square) & !blocked,
+        PieceKind::Queen => get_queen_attacks(occupancy, square) & !blocked,
+        PieceKind::King => king_attacks(square) & !blocked,
     }
-    1u64 << en_passant
 }
 
-fn get_pawn_moves(board: GeneratorBoard, square: u64, en_passant: u64) -> u64 {
-    let white = board.board.white_pieces() & (1u64 << square) != 0;
-    let target_pieces = if white { board.board.black_pieces() } else { board.board.white_pieces() };
-    let target_bishop = (board.board.bitboards[BISHOP] | board.board.bitboards[QUEEN]) & target_pieces;
-    let target_rook = (board.board.bitboards[ROOK] | board.board.bitboards[QUEEN]) & target_pieces;
+// Adds a move from square to each target, working out captures, promotions and pawn specials
+fn add_piece_moves(board: &Board, square: Square, targets: u64, moves: &mut MoveList) {
+    let piece = find_piece_type(board, square);
+    for pm_square in squares(targets) {
+        let capture = board.all_pieces() & pm_square.bit() != 0;
 
-    let passant_square = if square > 15 && square < 48 {
-        check_en_passant(&board, square, en_passant)
-    } else { 0 };
-
-    fn right_attacks(board: &Board, square: u64, en_passant: u64) -> u64 {
-        if board.white_pieces() & (1u64 << square) != 0 {
-            if square % 8 < 7 {
-                (1u64 << (square + 9)) & (board.black_pieces() | en_passant)
-            } else { 0 }
-        } else if square % 8 < 7 {
-            (1u64 << (square - 7)) & (board.white_pieces() | en_passant)
-        } else { 0 }
-    }
-
-    fn left_attacks(board: &Board, square: u64, en_passant: u64) -> u64 {
-        if board.white_pieces() & (1u64 << square) != 0 {
-            if square % 8 > 0 {
-                (1u64 << (square + 7)) & (board.black_pieces() | en_passant)
-            } else { 0 }
-        } else if square % 8 > 0 {
-            (1u64 << (square - 9)) & (board.white_pieces() | en_passant)
-        } else { 0 }
-    }
-
-    fn forward_moves(board: &Board, square: u64) -> u64 {
-        let mut moves: u64 = 0;
-        if board.white_pieces() & (1u64 << square) != 0 {
-            if (1u64 << (square + 8)) & board.all_pieces() == 0 {
-                moves |= 1u64 << (square + 8);
-                if square / 8 == 1 && board.all_pieces() & (1u64 << (square + 16)) == 0 {
-                    moves |= 1u64 << (square + 16); // can move double
-                }
-            }
-        } else if (1u64 << (square - 8)) & board.all_pieces() == 0 {
-            moves |= 1u64 << (square - 8);
-            if square / 8 == 6 && board.all_pieces() & (1u64 << (square - 16)) == 0 {
-                moves |= 1u64 << (square - 16); // can move double
+        // Deal with promotion
+        if piece.kind == PieceKind::Pawn && pm_square.rank() == Rank::Eighth.relative(piece.color) {
+            for promotion in Promotion::ALL {
+                moves.push(Move::new_promotion(square, pm_square, promotion, capture));
             }
-        }
-
-        moves
-    }
-
-    fn all_pawn_moves(board: GeneratorBoard, square: u64, en_passant: u64) -> u64 {
-        right_attacks(board.board, square, en_passant) |
-            left_attacks(board.board, square, en_passant) |
-            forward_moves(board.board, square)
-    }
-
-    if is_king_top_left(&board, square, white) {
-        if piece_on_bottom_right(&board, square, target_bishop) {
-            right_attacks(board.board, square, passant_square)
-        } else { all_pawn_moves(board, square, passant_square) }
-    } else if is_king_top_right(&board, square, white) {
-        if piece_on_bottom_left(&board, square, target_bishop) {
-            left_attacks(board.board, square, passant_square)
         } else {
-            all_pawn_moves(board, square, passant_square)
-        }
-    } else if is_king_bottom_left(&board, square, white) {
-        if piece_on_top_right(&board, square, target_bishop) {
-            right_attacks(board.board, square, passant_square)
-        } else {
-            all_pawn_moves(board, square, passant_square)
-        }
-    } else if is_king_bottom_right(&board, square, white) {
-        if piece_on_top_left(&board, square, target_bishop) {
-            left_attacks(board.board, square, passant_square)
-        } else {
-            all_pawn_moves(board, square, passant_square)
-        }
-    } else if is_king_left(&board, square, white) {
-        if piece_on_right(&board, square, target_rook) {
-            0 // no moves
-        } else {
-            all_pawn_moves(board, square, passant_square)
-        }
-    } else if is_king_right(&board, square, white) {
-        if piece_on_left(&board, square, target_rook) {
-            0 // no moves
-        } else {
-            all_pawn_moves(board, square, passant_square)
-        }
-    } else if is_king_top(&board, square, white) {
-        if piece_on_bottom(&board, square, target_rook) {
-            forward_moves(board.board, square)
-        } else {
-            all_pawn_moves(board, square, passant_square)
-        }
-    } else if is_king_bottom(&board, square, white) {
-        if piece_on_top(&board, square, target_rook) {
-            forward_moves(board.board, square)
-        } else {
-            all_pawn_moves(board, square, passant_square)
+            moves.push(Move::new(square, pm_square, if capture {
+                Move::CAPTURE
+            } else if is_en_passant(board, square, pm_square) {
+                Move::EN_PASSANT
+            } else if piece.kind == PieceKind::Pawn && square.rank().index().abs_diff(pm_square.rank().index()) == 2 {
+                Move::DOUBLE_PAWN_PUSH
+            } else {
+                Move::QUIET
+            }));
         }
-    } else {
-        all_pawn_moves(board, square, passant_square)
     }
 }
 
-fn king_diag_pinned(board: &GeneratorBoard, square: u64) -> bool {
-    let white = board.board.white_pieces() & (1u64 << square) != 0;
-    let target_bishop = (board.board.bitboards[BISHOP] | board.board.bitboards[QUEEN]) &
-        (if white { board.board.black_pieces() } else { board.board.white_pieces() });
-
-    (is_king_top_left(board, square, white) && piece_on_bottom_right(board, square, target_bishop)) ||
-        (is_king_top_right(board, square, white) && piece_on_bottom_left(board, square, target_bishop)) ||
-        (is_king_bottom_left(board, square, white) && piece_on_top_right(board, square, target_bishop)) ||
-        (is_king_bottom_right(board, square, white) && piece_on_top_left(board, square, target_bishop))
-}
+fn add_castling(board: &Board, castle_rights: CastleRights, color: Color, attacks: u64, moves: &mut MoveList) {
+    fn check_castling(board: &Board, castle_rights: CastleRights, side: CastleRights, king_square: Square, direction: i32, attacks: u64) -> bool {
+        let rook_square = match side {
+            CastleRights::WHITE_KING_SIDE => Square::H1,
+            CastleRights::WHITE_QUEEN_SIDE => Square::A1,
+            CastleRights::BLACK_QUEEN_SIDE => Square::A8,
+            _ => Square::H8,
+        };
+        let step = |count: i32| king_square.offset(direction * count, 0).map_or(0, |square| square.bit());
+        let pieces = board.all_pieces();
 
-//noinspection DuplicatedCode
-fn get_knight_moves(board: GeneratorBoard, square: u64) -> u64 {
-    let white = board.board.white_pieces() & (1u64 << square) != 0;
-    let target_pieces = if white { board.board.black_pieces() } else { board.board.white_pieces() };
-    let target_rook = (board.board.bitboards[ROOK] | board.board.bitboards[QUEEN]) & target_pieces;
-
-    if king_diag_pinned(&board, square) ||
-        (is_king_left(&board, square, white) && piece_on_right(&board, square, target_rook)) ||
-        (is_king_right(&board, square, white) && piece_on_left(&board, square, target_rook)) ||
-        (is_king_top(&board, square, white) && piece_on_bottom(&board, square, target_rook)) ||
-        (is_king_bottom(&board, square, white) && piece_on_top(&board, square, target_rook))
-    {
-        0 // knight can't move when pinned
-    } else if white {
-        get_knight_attacks(square) & (!board.board.white_pieces()) // Don't move onto own pieces
-    } else {
-        get_knight_attacks(square) & (!board.board.black_pieces())
+        castle_rights.contains(side) &&
+            (board.pieces(PieceKind::King) & board.colored(side_color(side)) & king_square.bit()) != 0 && // Ensure king is on it's starting square
+            (board.pieces(PieceKind::Rook) & board.colored(side_color(side)) & rook_square.bit()) != 0 && // Ensure rook is available to castle with
+            (pieces & (step(1) | step(2))) == 0 && // No pieces in between
+            // make sure QueenSide castling is possible
+            (direction > 0 || pieces & step(3) == 0) &&
+            (attacks & (king_square.bit() | step(1) | step(2))) == 0 // No checks in between
     }
-}
 
-fn get_bishop_moves(board: GeneratorBoard, square: u64) -> u64 {
-    let white = board.board.white_pieces() & (1u64 << square) != 0;
-    let target_pieces = if white { board.board.black_pieces() } else { board.board.white_pieces() };
-    let target_bishop = (board.board.bitboards[BISHOP] | board.board.bitboards[QUEEN]) & target_pieces;
-    let target_rook = (board.board.bitboards[ROOK] | board.board.bitboards[QUEEN]) & target_pieces;
-
-    if (is_king_left(&board, square, white) && piece_on_right(&board, square, target_rook)) ||
-        (is_king_right(&board, square, white) && piece_on_left(&board, square, target_rook)) ||
-        (is_king_top(&board, square, white) && piece_on_bottom(&board, square, target_rook)) ||
-        (is_king_bottom(&board, square, white) && piece_on_top(&board, square, target_rook)) {
-        0 // bishop can't move when pinned from the side
-    } else if is_king_top_left(&board, square, white) {
-        if piece_on_bottom_right(&board, square, target_bishop) {
-            top_left_bottom_right(board.board, square)
-        } else {
-            all_bishop_moves(board, square)
-        }
-    } else if is_king_top_right(&board, square, white) {
-        if piece_on_bottom_left(&board, square, target_bishop) {
-            top_right_bottom_left(board.board, square)
-        } else {
-            all_bishop_moves(board, square)
-        }
-    } else if is_king_bottom_left(&board, square, white) {
-        if piece_on_top_right(&board, square, target_bishop) {
-            top_right_bottom_left(board.board, square)
-        } else {
-            all_bishop_moves(board, square)
-        }
-    } else if is_king_bottom_right(&board, square, white) {
-        if piece_on_top_left(&board, square, target_bishop) {
-            top_left_bottom_right(board.board, square)
-        } else {
-            all_bishop_moves(board, square)
-        }
-    } else {
-        all_bishop_moves(board, square)
+    fn side_color(side: CastleRights) -> Color {
+        if CastleRights::for_color(Color::White).contains(side) { Color::White } else { Color::Black }
     }
-}
 
-fn get_rook_moves(board: GeneratorBoard, square: u64) -> u64 {
-    let white = board.board.white_pieces() & (1u64 << square) != 0;
-    let target_pieces = if white { board.board.black_pieces() } else { board.board.white_pieces() };
-    let target_rook = (board.board.bitboards[ROOK] | board.board.bitboards[QUEEN]) & target_pieces;
-
-    if king_diag_pinned(&board, square)
-    {
-        0 // rook can't move when pinned diagonally
-    } else if is_king_left(&board, square, white) {
-        if piece_on_right(&board, square, target_rook) {
-            left_right(board.board, square)
-        } else {
-            all_rook_moves(board, square)
-        }
-    } else if is_king_right(&board, square, white) {
-        if piece_on_left(&board, square, target_rook) {
-            left_right(board.board, square)
-        } else {
-            all_rook_moves(board, square)
-        }
-    } else if is_king_top(&board, square, white) {
-        if piece_on_bottom(&board, square, target_rook) {
-            top_bottom(board.board, square)
-        } else {
-            all_rook_moves(board, square)
-        }
-    } else if is_king_bottom(&board, square, white) {
-        if piece_on_top(&board, square, target_rook) {
-            top_bottom(board.board, square)
-        } else {
-            all_rook_moves(board, square)
+    // Castling
+    let (king_side, queen_side, king_square) = match color {
+        Color::White => (CastleRights::WHITE_KING_SIDE, CastleRights::WHITE_QUEEN_SIDE, WHITE_KING_SQUARE),
+        Color::Black => (CastleRights::BLACK_KING_SIDE, CastleRights::BLACK_QUEEN_SIDE, BLACK_KING_SQUARE),
+    };
+    for (side, direction, flag) in [(king_side, 1, Move::KING_CASTLE), (queen_side, -1, Move::QUEEN_CASTLE)] {
+        if check_castling(board, castle_rights, side, king_square, direction, attacks) {
+            moves.push(Move::new(king_square, king_square.offset(direction * 2, 0).unwrap(), flag));
         }
-    } else {
-        all_rook_moves(board, square) // moves
     }
 }
 
-fn get_queen_moves(board: GeneratorBoard, square: u64) -> u64 {
-    get_bishop_moves(board, square) | get_rook_moves(board, square)
+// A pawn moving diagonally onto an empty square can only be taking en passant
+fn is_en_passant(board: &Board, from: Square, to: Square) -> bool {
+    find_piece_type(board, from).kind == PieceKind::Pawn && from.file() != to.file() &&
+        (board.all_pieces() & to.bit()) == 0
 }
 
-fn get_king_moves(board: GeneratorBoard, square: u64) -> u64 {
-    let white = board.board.white_pieces() & (1u64 << square) != 0;
-    let target_pieces = if white { board.board.black_pieces() } else { board.board.white_pieces() };
-    let target_bishop = (board.board.bitboards[BISHOP] | board.board.bitboards[QUEEN]) & target_pieces;
-    let target_rook = (board.board.bitboards[ROOK] | board.board.bitboards[QUEEN]) & target_pieces;
-    let mut attacks = if white { board.black_attacks } else { board.white_attacks };
-
-    if piece_on_top_left(&board, square, target_bishop) {
-        attacks |= if square > 6 { 1u64 << (square - 7) } else { 0 }; // Bottom Right
-    }
-    if piece_on_top_right(&board, square, target_bishop) {
-        attacks |= if square > 8 { 1u64 << (square - 9) } else { 0 }; // Bottom Left
-    }
-    if piece_on_bottom_left(&board, square, target_bishop) {
-        attacks |= if square < 55 { 1u64 << (square + 9) } else { 0 }; // Top Right
-    }
-    if piece_on_bottom_right(&board, square, target_bishop) {
-        attacks |= if square < 57 { 1u64 << (square + 7) } else { 0 }; // Top Left
-    }
-    if piece_on_left(&board, square, target_rook) {
-        attacks |= if square > 0 { 1u64 << (square - 1) } else { 0 }; // Right
-    }
-    if piece_on_right(&board, square, target_rook) {
-        attacks |= if square < 63 { 1u64 << (square + 1) } else { 0 }; // Left
-    }
-    if piece_on_top(&board, square, target_rook) {
-        attacks |= if square > 7 { 1u64 << (square - 8) } else { 0 }; // Bottom
-    }
-    if piece_on_bottom(&board, square, target_rook) {
-        attacks |= if square < 56 { 1u64 << (square + 8) } else { 0 }; // Top
+// Squares in the direction up to and including the first piece
+fn ray(pieces: u64, square: Square, direction: (i32, i32)) -> u64 {
+    let mut attacks: u64 = 0;
+    let mut loc = square;
+    while let Some(next) = loc.offset(direction.0, direction.1) {
+        attacks |= next.bit();
+        if pieces & next.bit() != 0 { break; }
+        loc = next;
     }
 
-    get_king_attacks(square) & (!if white { board.board.white_pieces() } else { board.board.black_pieces() }) & (!attacks)
+    attacks
 }
 
-fn get_pawn_attacks(board: &Board, square: u64) -> u64 {
-    if board.white_pieces() & (1u64 << square) != 0 {
-        (if square % 8 > 0 { 1u64 << (square + 7) } else { 0 }) |
-            (if square % 8 < 7 { 1u64 << (square + 9) } else { 0 })
+fn own_pieces(board: &Board, square: Square) -> u64 {
+    if board.white_pieces() & square.bit() != 0 {
+        board.white_pieces()
     } else {
-        (if square % 8 < 7 { 1u64 << (square - 7) } else { 0 }) |
-            (if square % 8 > 0 { 1u64 << (square - 9) } else { 0 })
+        board.black_pieces()
     }
 }
 
-fn get_knight_attacks(square: u64) -> u64 {
-    let mut attacks: u64 = 0;
-
-    if square / 8 > 1 {
-        attacks |= if square % 8 > 0 { 1u64 << (square - 17) } else { 0 } |
-            if square % 8 < 7 { 1u64 << (square - 15) } else { 0 }
-    }
-
-    if square / 8 > 0 {
-        attacks |= if square % 8 > 1 { 1u64 << (square - 10) } else { 0 } |
-            if square % 8 < 6 { 1u64 << (square - 6) } else { 0 }
-    }
-
-    if square / 8 < 7 {
-        attacks |= if square % 8 > 1 { 1u64 << (square + 6) } else { 0 } |
-            if square % 8 < 6 { 1u64 << (square + 10) } else { 0 }
-    }
-
-    if square / 8 < 6 {
-        attacks |= if square % 8 > 0 { 1u64 << (square + 15) } else { 0 } |
-            if square % 8 < 7 { 1u64 << (square + 17) } else { 0 }
-    }
-
-    attacks
+fn color_on(board: &Board, square: Square) -> Color {
+    if board.white_pieces() & square.bit() != 0 { Color::White } else { Color::Black }
 }
 
-
-fn get_rook_attacks(pieces: u64, square: u64) -> u64 {
-    let mut attacks: u64 = 0;
-    let mut loc: u64 = square;
-
-    while loc < 56 { // Top
-        loc += 8;
-        attacks |= 1u64 << loc;
-
-        if pieces & (1u64 << loc) != 0 { break; }
-    }
-
-    loc = square;
-    while loc > 7 { // Bottom
-        loc -= 8;
-        attacks |= 1u64 << loc;
-
-        if pieces & (1u64 << loc) != 0 { break; }
-    }
-
-    loc = square;
-    while loc % 8 > 0 { // Right
-        loc -= 1;
-        attacks |= 1u64 << loc;
-
-        if pieces & (1u64 << loc) != 0 { break; }
-    }
-
-    loc = square;
-    while loc % 8 < 7 { // Left
-        loc += 1;
-        attacks |= 1u64 << loc;
-
-        if pieces & (1u64 << loc) != 0 { break; }
+// Taking en passant removes two pieces from the rank, which can uncover a check no pin shows
+fn check_en_passant(board: &GeneratorBoard, square: Square, en_passant: Option<Square>) -> u64 {
+    let Some(en_passant) = en_passant else {
+        return 0;
+    };
+    let color = color_on(board.board, square);
+    let taken = Square::new(en_passant.file(), square.rank());
+    let occupancy = (board.board.all_pieces() & !square.bit() & !taken.bit()) | en_passant.bit();
+    let enemies = board.board.colored(!color) & !taken.bit();
+    let king = board.king(color);
+
+    if (get_rook_attacks(occupancy, king) & enemies & (board.board.pieces(PieceKind::Rook) | board.board.pieces(PieceKind::Queen))) |
+        (get_bishop_attacks(occupancy, king) & enemies & (board.board.pieces(PieceKind::Bishop) | board.board.pieces(PieceKind::Queen))) != 0 {
+        return 0;
     }
-
-    attacks
+    en_passant.bit()
 }
 
-fn get_bishop_attacks(pieces: u64, square: u64) -> u64 {
-    let mut attacks: u64 = 0;
-    let mut loc: u64 = square;
-
-    while loc > 7 && loc % 8 > 0 && loc > 8 { // Bottom Right
-        loc -= 9;
-        attacks |= 1u64 << loc;
-
-        if pieces & (1u64 << loc) != 0 { break; }
-    }
-
-    loc = square;
-    while loc > 7 && loc % 8 < 7 && loc > 6 { // Bottom Left
-        loc -= 7;
-        attacks |= 1u64 << loc;
+fn get_pawn_moves(board: GeneratorBoard, square: Square, en_passant: Option<Square>) -> u64 {
+    let color = color_on(board.board, square);
 
-        if pieces & (1u64 << loc) != 0 { break; }
-    }
+    let passant_square = if (2..=5).contains(&square.rank().index()) {
+        check_en_passant(&board, square, en_passant)
+    } else { 0 };
+    let attacks = pawn_attacks(color, square) & (board.board.colored(!color) | passant_square);
 
-    loc = square;
-    while loc < 57 && loc % 8 > 0 && loc < 58 { // Top Left
-        loc += 7;
-        attacks |= 1u64 << loc;
+    (attacks | pawn_pushes(board.board, color, square)) & board.pin_mask(square)
+}
 
-        if pieces & (1u64 << loc) != 0 { break; }
+fn pawn_pushes(board: &Board, color: Color, square: Square) -> u64 {
+    let all_pieces = board.all_pieces();
+    let (direction, start_rank) = if color == Color::White { (1, Rank::Second) } else { (-1, Rank::Seventh) };
+    let mut forward = 0;
+    if let Some(single) = square.offset(0, direction) {
+        if single.bit() & all_pieces == 0 {
+            forward |= single.bit();
+            if square.rank() == start_rank {
+                let double = square.offset(0, direction * 2).unwrap();
+                if all_pieces & double.bit() == 0 {
+                    forward |= double.bit(); // can move double
+                }
+            }
+        }
     }
 
-    loc = square;
-    while loc < 57 && loc % 8 < 7 && loc < 56 { // Top Right
-        loc += 9;
-        attacks |= 1u64 << loc;
+    forward
+}
 
-        if pieces & (1u64 << loc) != 0 { break; }
+fn get_knight_moves(board: GeneratorBoard, square: Square) -> u64 {
+    if board.pin_mask(square) != !0 {
+        0 // knight can't move when pinned
+    } else {
+        knight_attacks(square) & !own_pieces(board.board, square) // Don't move onto own pieces
     }
-
-    attacks
 }
 
+fn get_bishop_moves(board: GeneratorBoard, square: Square) -> u64 {
+    get_bishop_attacks(board.board.all_pieces(), square) & !own_pieces(board.board, square) & board.pin_mask(square)
+}
 
-fn get_queen_attacks(pieces: u64, square: u64) -> u64 {
-    get_bishop_attacks(pieces, square) | get_rook_attacks(pieces, square) // ez
+fn get_rook_moves(board: GeneratorBoard, square: Square) -> u64 {
+    get_rook_attacks(board.board.all_pieces(), square) & !own_pieces(board.board, square) & board.pin_mask(square)
 }
 
-fn get_king_attacks(square: u64) -> u64 {
-    let mut attacks: u64 = if square < 56 { 1u64 << (square + 8) } else { 0 } |
-        if square > 7 { 1u64 << (square - 8) } else { 0 }; // Top and Bottom
+fn get_queen_moves(board: GeneratorBoard, square: Square) -> u64 {
+    get_bishop_moves(board, square) | get_rook_moves(board, square)
+}
 
-    if square % 8 < 7 { // Left
-        attacks |= if square < 55 { 1u64 << (square + 9) } else { 0 };
-        attacks |= if square < 64 { 1u64 << (square + 1) } else { 0 };
-        attacks |= if square > 6 { 1u64 << (square - 7) } else { 0 };
-    }
-    if square % 8 > 0 { // Right
-        attacks |= if square < 57 { 1u64 << (square + 7) } else { 0 };
-        attacks |= if square > 0 { 1u64 << (square - 1) } else { 0 };
-        attacks |= if square > 8 { 1u64 << (square - 9) } else { 0 };
-    }
+fn get_king_moves(board: GeneratorBoard, square: Square) -> u64 {
+    let color = color_on(board.board, square);
+    king_attacks(square) & !own_pieces(board.board, square) & !board.attacks_by(!color)
+}
 
-    attacks
+fn get_rook_attacks(pieces: u64, square: Square) -> u64 {
+    magic::rook_attacks(square, pieces)
 }
 
-fn get_between(board: &GeneratorBoard, start: u64, end: u64) -> u64 {
-    let mut between: u64 = 0;
-    let mut loc: u64 = start;
-    let target = 1u64 << end;
+fn get_bishop_attacks(pieces: u64, square: Square) -> u64 {
+    magic::bishop_attacks(square, pieces)
+}
 
-    if piece_on_top_left(board, start, target) {
-        while loc / 8 < 7 && loc % 8 > 0 && loc < 58 { // Top Left
-            loc += 7;
+// Ray walking versions, the magic tables are built from and tested against these
+pub(crate) fn ray_rook_attacks(pieces: u64, square: Square) -> u64 {
+    ray(pieces, square, NORTH) | ray(pieces, square, SOUTH) | ray(pieces, square, EAST) | ray(pieces, square, WEST)
+}
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
-        }
-    } else if piece_on_top_right(board, start, target) {
-        while loc / 8 < 7 && loc % 8 < 7 && loc < 56 { // Top Right
-            loc += 9;
+pub(crate) fn ray_bishop_attacks(pieces: u64, square: Square) -> u64 {
+    ray(pieces, square, NORTH_EAST) | ray(pieces, square, NORTH_WEST) | ray(pieces, square, SOUTH_EAST) | ray(pieces, square, SOUTH_WEST)
+}
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
-        }
-    } else if piece_on_bottom_left(board, start, target) {
-        while loc / 8 > 0 && loc % 8 > 0 && loc > 8 { // Bottom Left
-            loc -= 9;
+fn get_queen_attacks(pieces: u64, square: Square) -> u64 {
+    get_bishop_attacks(pieces, square) | get_rook_attacks(pieces, square) // ez
+}
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+    use crate::board::create_board_from_string;
+    use crate::moves::{get_moves, get_moves_into, get_moves_of_type_into, GenType, Move, MoveList, Undo};
+    use crate::position::Position;
+    use crate::position::create_position_from_fen;
+    use crate::types::{CastleRights, Color, Promotion, Square};
+
+    #[test]
+    fn test_move_packing() {
+        assert_eq!(size_of::<Move>(), 2);
+        assert!(size_of::<Undo>() <= 4);
+
+        let e2: Square = "e2".parse().unwrap();
+        let e4: Square = "e4".parse().unwrap();
+        let push = Move::new(e2, e4, Move::DOUBLE_PAWN_PUSH);
+        assert_eq!((push.from(), push.to()), (e2, e4));
+        assert!(push.is_double_pawn_push() && !push.is_capture() && !push.is_castle());
+        assert_eq!(Move::from_bits(push.bits()), push);
+
+        let b7: Square = "b7".parse().unwrap();
+        for promotion in Promotion::ALL {
+            let promote = Move::new_promotion(b7, Square::A8, promotion, true);
+            assert_eq!((promote.from(), promote.to()), (b7, Square::A8));
+            assert_eq!(promote.promotion(), Some(promotion));
+            assert!(promote.is_capture() && !promote.is_en_passant());
+        }
+
+        let take = Move::new("e5".parse().unwrap(), "d6".parse().unwrap(), Move::EN_PASSANT);
+        assert!(take.is_en_passant() && take.is_capture() && take.promotion().is_none());
+        assert!(Move::new(Square::E1, Square::C1, Move::QUEEN_CASTLE).is_castle());
+    }
+
+    #[test]
+    fn test_undo_restores_state() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let mut position = create_position_from_fen(fen);
+            for move_ in get_moves(&position.board, position.en_passant, position.castle_rights, position.side_to_move) {
+                let undo = move_.make_move(&mut position.board, &mut position.castle_rights, &mut position.en_passant);
+                move_.unmake_move(&mut position.board, &mut position.castle_rights, &mut position.en_passant, undo);
+                assert_eq!(position.to_fen(), fen);
+                assert_eq!(position.board.bitboards, create_position_from_fen(fen).board.bitboards);
+            }
         }
-    } else if piece_on_bottom_right(board, start, target) {
-        while loc / 8 > 0 && loc % 8 < 7 && loc > 6 { // Bottom Right
-            loc -= 7;
+    }
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
+    #[test]
+    fn test_move_list() {
+        let mut position = create_position_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let mut list = MoveList::new();
+        list.push(Move::new(Square::A1, Square::B1, Move::QUIET)); // cleared by get_moves_into
+        get_moves_into(&position.board, position.en_passant, position.castle_rights, position.side_to_move, &mut list);
+
+        let moves = get_moves(&position.board, position.en_passant, position.castle_rights, position.side_to_move);
+        assert_eq!(list.len(), 48);
+        assert_eq!(list.as_slice(), moves.as_slice());
+        assert!(moves.iter().all(|&move_| list.contains(move_)));
+
+        for index in 0..list.len() {
+            let score = if list[index].is_capture() { 100 + index as i32 } else { 0 };
+            list.set_score(index, score);
+        }
+        let best = list.pick_best(0);
+        assert!(best.is_capture());
+        assert_eq!(list.score(0), list.iter().enumerate().map(|(index, _)| list.score(index)).max().unwrap());
+
+        list.sort_by_score();
+        assert!((1..list.len()).all(|index| list.score(index - 1) >= list.score(index)));
+        assert_eq!(list.iter().filter(|move_| move_.is_capture()).count(), 8);
+        assert!(list.iter().take(8).all(|move_| move_.is_capture()));
+
+        // Generating from inside a search reuses the same list
+        position.push(list[0]);
+        position.legal_moves_into(&mut list);
+        assert_eq!(list.as_slice(), position.legal_moves().as_slice());
+    }
+
+    fn of_type(position: &Position, gen_type: GenType) -> Vec<Move> {
+        let mut list = MoveList::new();
+        position.moves_of_type_into(gen_type, &mut list);
+        list.as_slice().to_vec()
+    }
+
+    // Walks the tree checking the staged types split up the full move list
+    fn verify_gen_types(position: &mut Position, depth: u32) {
+        let all = position.legal_moves();
+        let captures = of_type(position, GenType::Captures);
+        let quiets = of_type(position, GenType::Quiets);
+        let evasions = of_type(position, GenType::Evasions);
+        let quiet_checks = of_type(position, GenType::QuietChecks);
+
+        assert_eq!(captures.len() + quiets.len(), all.len(), "{}", position.to_fen());
+        assert!(captures.iter().all(|move_| all.contains(move_) && (move_.is_capture() || move_.promotion().is_some())));
+        assert!(quiets.iter().all(|move_| all.contains(move_) && !move_.is_capture() && move_.promotion().is_none()));
+        assert_eq!(evasions, if position.in_check() { all.clone() } else { Vec::new() });
+
+        let expected_checks: Vec<Move> = quiets.iter().copied().filter(|&move_| {
+            position.push(move_);
+            let check = position.in_check();
+            position.pop();
+            check
+        }).collect();
+        assert_eq!(quiet_checks, expected_checks, "{}", position.to_fen());
+
+        if depth > 0 {
+            for move_ in all {
+                position.push(move_);
+                verify_gen_types(position, depth - 1);
+                position.pop();
+            }
         }
-    } else if piece_on_left(board, start, target) {
-        while loc % 8 < 7 { // Left
-            loc += 1;
+    }
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
+    #[test]
+    fn test_gen_types() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // Castling into check, and pieces and a pawn uncovering checks
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "4k3/8/8/4N3/8/8/8/4RK2 w - - 0 1",
+            "7k/8/8/8/8/2P5/1B6/4R1K1 w - - 0 1",
+            "8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1",
+        ] {
+            verify_gen_types(&mut create_position_from_fen(fen), 2);
+        }
+    }
+
+    #[test]
+    fn test_no_king() {
+        // A bare board can lack a king, a1 being attacked mustn't count as check
+        let board = create_board_from_string("r7/8/8/8/8/8/4P3/8");
+        assert_eq!(get_moves(&board, None, CastleRights::NONE, Color::White).len(), 2);
+        let mut moves = MoveList::new();
+        get_moves_of_type_into(&board, None, CastleRights::NONE, Color::White, GenType::Evasions, &mut moves);
+        assert_eq!(moves.len(), 0);
+    }
+
+    // Walks the tree checking pseudo legal moves filtered by is_legal give the legal moves
+    fn verify_pseudo_legal(position: &mut Position, depth: u32) {
+        let mut pseudo = MoveList::new();
+        position.pseudo_legal_moves_into(&mut pseudo);
+        assert!(pseudo.iter().all(|move_| position.is_pseudo_legal(move_)), "{}", position.to_fen());
+
+        let legal: Vec<Move> = pseudo.iter().filter(|&move_| position.is_legal(move_)).collect();
+        let mut expected = position.legal_moves();
+        let mut found = legal.clone();
+        expected.sort_by_key(|move_| move_.bits());
+        found.sort_by_key(|move_| move_.bits());
+        assert_eq!(found, expected, "{}", position.to_fen());
+
+        if depth > 0 {
+            for move_ in legal {
+                position.push(move_);
+                verify_pseudo_legal(position, depth - 1);
+                position.pop();
+            }
         }
-    } else if piece_on_right(board, start, target) {
-        while loc % 8 > 0 { // Right
-            loc -= 1;
+    }
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
+    #[test]
+    fn test_pseudo_legal() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            verify_pseudo_legal(&mut create_position_from_fen(fen), 2);
         }
-    } else if piece_on_top(board, start, target) {
-        while loc < 56 { // Top
-            loc += 8;
+    }
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
-        }
-    } else if piece_on_bottom(board, start, target) {
-        while loc > 7 { // Bottom
-            loc -= 8;
+    #[test]
+    fn test_is_pseudo_legal_every_move() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        ] {
+            let position = create_position_from_fen(fen);
+            let mut pseudo = MoveList::new();
+            position.pseudo_legal_moves_into(&mut pseudo);
 
-            if board.board.all_pieces() & (1u64 << loc) != 0 { break; }
-            between |= 1u64 << loc;
+            // Every possible 16 bit move, including ones with nonsense flags
+            for bits in 0..=u16::MAX {
+                let move_ = Move::from_bits(bits);
+                assert_eq!(position.is_pseudo_legal(move_), pseudo.contains(move_), "{} in {}", bits, fen);
+            }
         }
-    } else {
-        board.board.print_board();
-        panic!("No way to get between squares {} and {}", square_to_algebraic(start), square_to_algebraic(end));
     }
-
-    between
-}
\ No newline at end of file
+}
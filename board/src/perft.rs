@@ -0,0 +1,487 @@
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread; // Speed up perft
+use crate::board::Board;
+use crate::moves::{self, Move};
+use crate::position::Position;
+
+// The columns of the usual perft tables, all counted over the moves at the last ply
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PerftStats {
+    pub capture_count: u64,
+    pub promotion_count: u64,
+    pub check_count: u64,
+    pub total_count: u64,
+    pub castle_count: u64,
+    pub en_passant_count: u64,
+    pub discovery_check_count: u64, // single checks by a piece other than the one that moved
+    pub double_check_count: u64,
+    pub checkmate_count: u64,
+}
+
+impl Add for PerftStats {
+    type Output = PerftStats;
+
+    fn add(mut self, other: PerftStats) -> PerftStats {
+        self += other;
+        self
+    }
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, other: PerftStats) {
+        self.capture_count += other.capture_count;
+        self.promotion_count += other.promotion_count;
+        self.check_count += other.check_count;
+        self.total_count += other.total_count;
+        self.castle_count += other.castle_count;
+        self.en_passant_count += other.en_passant_count;
+        self.discovery_check_count += other.discovery_check_count;
+        self.double_check_count += other.double_check_count;
+        self.checkmate_count += other.checkmate_count;
+    }
+}
+
+impl Sum for PerftStats {
+    fn sum<I: Iterator<Item = PerftStats>>(iter: I) -> PerftStats {
+        iter.fold(PerftStats::default(), |total, stats| total + stats)
+    }
+}
+
+// Perft split by root move, here depth 1 is just the legal moves
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PerftReport {
+    pub depth: u32,
+    pub divide: Vec<(Move, PerftStats)>, // in move generation order
+    pub total: PerftStats,
+}
+
+// Called with each root move once everything under it has been counted
+pub type PerftProgress<'a> = &'a mut dyn FnMut(Move, &PerftStats);
+
+// Runs perft one root move at a time, calling progress after each one finishes
+pub fn perft(position: &mut Position, depth: u32, mut progress: Option<PerftProgress>) -> PerftReport {
+    let mut report = PerftReport { depth, divide: Vec::new(), total: PerftStats::default() };
+    if depth == 0 {
+        report.total.total_count = 1;
+        return report;
+    }
+
+    let move_list = moves::get_moves(&position.board, position.en_passant, position.castle_rights, position.side_to_move);
+    for move_ in move_list {
+        let result = root_move_stats(position, move_, depth);
+        if let Some(progress) = progress.as_mut() {
+            progress(move_, &result);
+        }
+        report.total += result;
+        report.divide.push((move_, result));
+    }
+
+    report
+}
+
+// Below this many jobs for each worker the root moves are split again at ply 2
+const MIN_JOBS_PER_THREAD: usize = 4;
+
+// Perft spread over a pool of workers, threads 0 uses every core. Idle workers take the next unstarted
+// job so the load evens out, and the report is the same as perft gives whatever order jobs finish in.
+pub fn perft_parallel(position: &Position, depth: u32, threads: usize, mut progress: Option<PerftProgress>) -> PerftReport {
+    let threads = if threads == 0 { thread::available_parallelism().map_or(1, |count| count.get()) } else { threads };
+    if threads == 1 || depth < 2 {
+        return perft(&mut position.clone(), depth, progress);
+    }
+
+    let root_moves = moves::get_moves(&position.board, position.en_passant, position.castle_rights, position.side_to_move);
+    let split = depth >= 3 && root_moves.len() < threads * MIN_JOBS_PER_THREAD;
+
+    // A job is a root move, or a root move and one reply when splitting
+    let mut totals = vec![PerftStats::default(); root_moves.len()];
+    let mut remaining = vec![1; root_moves.len()];
+    let mut jobs: Vec<(usize, Move, Option<Move>)> = Vec::new();
+    for (index, &move_) in root_moves.iter().enumerate() {
+        if split {
+            let mut position = position.clone();
+            position.push(move_);
+            let replies = position.legal_moves();
+            remaining[index] = replies.len();
+            jobs.extend(replies.into_iter().map(|reply| (index, move_, Some(reply))));
+        } else {
+            jobs.push((index, move_, None));
+        }
+    }
+
+    let next_job = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads.min(jobs.len()) {
+            let (tx, jobs, next_job) = (tx.clone(), &jobs, &next_job);
+            scope.spawn(move || {
+                let mut position = position.clone();
+                while let Some(&(index, move_, reply)) = jobs.get(next_job.fetch_add(1, Ordering::Relaxed)) {
+                    let result = match reply {
+                        Some(reply) => {
+                            position.push(move_);
+                            let result = recursive_count_moves(&mut position, reply, depth - 3);
+                            position.pop();
+                            result
+                        }
+                        None => root_move_stats(&mut position, move_, depth),
+                    };
+                    tx.send((index, result)).unwrap();
+                }
+            });
+        }
+        drop(tx);
+
+        // Root moves without replies are already finished
+        for (index, &move_) in root_moves.iter().enumerate() {
+            if remaining[index] == 0 {
+                if let Some(progress) = progress.as_mut() {
+                    progress(move_, &totals[index]);
+                }
+            }
+        }
+        for (index, val) in rx {
+            let perft = &mut totals[index];
+            *perft += val;
+            remaining[index] -= 1;
+            if remaining[index] == 0 {
+                if let Some(progress) = progress.as_mut() {
+                    progress(root_moves[index], perft);
+                }
+            }
+        }
+    });
+
+    let mut report = PerftReport { depth, divide: Vec::new(), total: PerftStats::default() };
+    for (move_, result) in root_moves.into_iter().zip(totals) {
+        report.total += result;
+        report.divide.push((move_, result));
+    }
+    report
+}
+
+// The depth here counts one less than perft, so depth 0 is the number of legal moves
+pub fn count_moves(position: &mut Position, depth: u32) -> PerftStats {
+    perft_parallel(position, depth + 1, 0, None).total
+}
+
+pub fn count_moves_no_threads(position: &mut Position, depth: u32) -> PerftStats {
+    perft(position, depth + 1, None).total
+}
+
+// Lockless node count cache shared between threads. An entry holds (key ^ data, data), so one
+// torn by two threads writing at once fails the key check instead of giving a wrong count.
+pub struct PerftTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl PerftTable {
+    // Rounded down to a power of two entries of 16 bytes each
+    pub fn new(megabytes: usize) -> PerftTable {
+        let count = (megabytes * 1024 * 1024 / 16).max(1);
+        let count = 1 << count.ilog2();
+        PerftTable { entries: (0..count).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&self) {
+        for entry in &self.entries {
+            entry[0].store(0, Ordering::Relaxed);
+            entry[1].store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Data is the node count above 8 bits of depth
+    pub fn probe(&self, hash: u64, depth: u32) -> Option<u64> {
+        let entry = &self.entries[hash as usize & (self.entries.len() - 1)];
+        let data = entry[1].load(Ordering::Relaxed);
+        let key = entry[0].load(Ordering::Relaxed) ^ data;
+        if data != 0 && key == hash && data & 0xFF == depth as u64 { Some(data >> 8) } else { None }
+    }
+
+    // Always replaces whatever was there
+    pub fn store(&self, hash: u64, depth: u32, nodes: u64) {
+        let entry = &self.entries[hash as usize & (self.entries.len() - 1)];
+        let data = (nodes << 8) | (depth as u64 & 0xFF);
+        entry[0].store(hash ^ data, Ordering::Relaxed);
+        entry[1].store(data, Ordering::Relaxed);
+    }
+}
+
+// Node count only, looking up and filling the table for every position two or more plies from the leaves.
+// Depth counts like perft, and the table can be shared by several threads at once.
+pub fn perft_hashed(position: &mut Position, depth: u32, table: &PerftTable) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let mut move_list = moves::MoveList::new();
+    if depth == 1 {
+        position.legal_moves_into(&mut move_list);
+        return move_list.len() as u64;
+    }
+
+    // Moves are only generated when the table can't answer
+    let hash = position.hash();
+    if let Some(nodes) = table.probe(hash, depth) {
+        return nodes;
+    }
+    position.legal_moves_into(&mut move_list);
+
+    let mut nodes = 0;
+    for n_move in &move_list {
+        position.push(n_move);
+        nodes += perft_hashed(position, depth - 1, table);
+        position.pop();
+    }
+    table.store(hash, depth, nodes);
+
+    nodes
+}
+
+// Everything under one root move, depth is at least 1
+fn root_move_stats(position: &mut Position, move_: Move, depth: u32) -> PerftStats {
+    if depth > 1 {
+        return recursive_count_moves(position, move_, depth - 2);
+    }
+
+    let perft = leaf_stats(position, move_);
+    debug_assert!(!check_overlap(&position.board), "Piece overlap caused by move {}", move_);
+    perft
+}
+
+// One node at the last ply, with its columns filled in
+fn leaf_stats(position: &mut Position, move_: Move) -> PerftStats {
+    let mut perft = PerftStats { total_count: 1, ..PerftStats::default() };
+
+    // Count information
+    if move_.is_capture() {
+        perft.capture_count += 1;
+    }
+    if move_.promotion().is_some() { perft.promotion_count += 1; }
+    if move_.is_castle() {
+        perft.castle_count += 1;
+    }
+    perft.en_passant_count += if move_.is_en_passant() { 1 } else { 0 };
+
+    position.push(move_);
+    let checkers = position.checkers();
+    if checkers != 0 {
+        perft.check_count += 1;
+
+        // After castling it's the rook that moved next to the king
+        let moved = if move_.is_castle() {
+            move_.to().offset(if move_.to().index() > move_.from().index() { -1 } else { 1 }, 0).unwrap()
+        } else {
+            move_.to()
+        };
+        if checkers.count_ones() > 1 {
+            perft.double_check_count += 1;
+        } else if checkers & !moved.bit() != 0 {
+            perft.discovery_check_count += 1;
+        }
+
+        let mut move_list = moves::MoveList::new();
+        position.legal_moves_into(&mut move_list);
+        if move_list.is_empty() {
+            perft.checkmate_count += 1;
+        }
+    }
+    position.pop();
+
+    perft
+}
+
+fn check_overlap(board: &Board) -> bool {
+    for piece in 0..6 {
+        for alt in 0..6 {
+            if piece == alt { continue; }
+            if board.bitboards[piece] & board.bitboards[alt] != 0 {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+// Everything depth + 2 plies deep that starts with move_
+fn recursive_count_moves(position: &mut Position, move_: Move, depth: u32) -> PerftStats {
+    position.push(move_);
+    let mut move_list = moves::MoveList::new();
+    position.legal_moves_into(&mut move_list);
+    let mut perft = PerftStats::default();
+
+    for n_move in &move_list {
+        if depth == 0 {
+            perft += leaf_stats(position, n_move);
+        } else {
+            perft += recursive_count_moves(position, n_move, depth - 1);
+        }
+    }
+    position.pop();
+
+    perft
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::perft::{perft, perft_hashed, perft_parallel, PerftStats, PerftTable};
+    use crate::position::{create_default_position, create_position_from_fen};
+
+    #[test]
+    fn test_perft_report() {
+        let mut position = create_default_position();
+        assert_eq!(perft(&mut position, 0, None).total.total_count, 1);
+        let report = perft(&mut position, 1, None);
+        assert_eq!((report.divide.len(), report.total.total_count), (20, 20));
+        assert!(report.divide.iter().all(|(_, stats)| stats.total_count == 1));
+
+        let mut finished = Vec::new();
+        let report = perft(&mut position, 3, Some(&mut |move_, stats| finished.push((move_, *stats))));
+        assert_eq!(report.total.total_count, 8_902);
+        assert_eq!(report.divide, finished);
+        assert_eq!(report.divide.iter().map(|(_, stats)| stats.total_count).sum::<u64>(), 8_902);
+        let divide = |uci: &str| report.divide.iter().find(|(move_, _)| move_.to_uci() == uci).unwrap().1.total_count;
+        assert_eq!((divide("e2e4"), divide("g1f3"), divide("a2a3")), (600, 440, 380));
+        assert_eq!(position.to_fen(), create_default_position().to_fen());
+
+        let mut kiwipete = create_position_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let report = perft(&mut kiwipete, 2, None);
+        assert_eq!((report.depth, report.divide.len(), report.total.total_count), (2, 48, 2_039));
+    }
+
+    #[test]
+    fn test_perft_parallel() {
+        // Kiwipete has 48 root moves and the rook ending 14, so some of these split at ply 2
+        for (fen, depth) in [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4),
+            ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3), // Ra8 is mate so it has no replies
+        ] {
+            let mut position = create_position_from_fen(fen);
+            let expected = perft(&mut position, depth, None);
+            for threads in [1, 2, 3, 8, 64] {
+                let mut finished = Vec::new();
+                let report = perft_parallel(&position, depth, threads, Some(&mut |move_, stats| finished.push((move_, *stats))));
+                assert_eq!(report, expected, "{} with {} threads", fen, threads);
+                finished.sort_by_key(|(move_, _)| expected.divide.iter().position(|(other, _)| other == move_));
+                assert_eq!(finished, expected.divide, "{} with {} threads", fen, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_hashed() {
+        let table = PerftTable::new(1);
+        assert_eq!(table.len(), 65_536);
+        // One entry, so almost every store replaces another position
+        let tiny = PerftTable::new(0);
+        assert_eq!(tiny.len(), 1);
+
+        for (fen, depth) in [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3),
+        ] {
+            let mut position = create_position_from_fen(fen);
+            let expected = perft(&mut position, depth, None).total.total_count;
+            for depth in 0..=depth {
+                assert_eq!(perft_hashed(&mut position, depth, &tiny), perft(&mut position, depth, None).total.total_count, "{}", fen);
+            }
+
+            // Second time round the root comes straight from the table
+            table.clear();
+            assert_eq!(perft_hashed(&mut position, depth, &table), expected, "{}", fen);
+            assert_eq!(table.probe(position.hash(), depth), Some(expected));
+            assert_eq!(perft_hashed(&mut position, depth, &table), expected, "{}", fen);
+            assert_eq!(position.to_fen(), create_position_from_fen(fen).to_fen());
+
+            // Several threads filling the same table at once
+            table.clear();
+            thread::scope(|scope| {
+                let table = &table;
+                let handles: Vec<_> = (0..4).map(|_| {
+                    let mut position = position.clone();
+                    scope.spawn(move || perft_hashed(&mut position, depth, table))
+                }).collect();
+                for handle in handles {
+                    assert_eq!(handle.join().unwrap(), expected, "{}", fen);
+                }
+            });
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn stats(total: u64, captures: u64, en_passants: u64, castles: u64, promotions: u64,
+             checks: u64, discovery_checks: u64, double_checks: u64, checkmates: u64) -> PerftStats {
+        PerftStats {
+            capture_count: captures,
+            promotion_count: promotions,
+            check_count: checks,
+            total_count: total,
+            castle_count: castles,
+            en_passant_count: en_passants,
+            discovery_check_count: discovery_checks,
+            double_check_count: double_checks,
+            checkmate_count: checkmates,
+        }
+    }
+
+    #[test]
+    fn test_perft_stats() {
+        // The chessprogramming wiki tables, every depth
+        let start = create_position_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let kiwipete = create_position_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let ending = create_position_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
+        let promotions = create_position_from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1");
+        for (position, expected) in [
+            (&start, vec![
+                stats(20, 0, 0, 0, 0, 0, 0, 0, 0),
+                stats(400, 0, 0, 0, 0, 0, 0, 0, 0),
+                stats(8_902, 34, 0, 0, 0, 12, 0, 0, 0),
+                stats(197_281, 1_576, 0, 0, 0, 469, 0, 0, 8),
+                stats(4_865_609, 82_719, 258, 0, 0, 27_351, 6, 0, 347),
+            ]),
+            (&kiwipete, vec![
+                stats(48, 8, 0, 2, 0, 0, 0, 0, 0),
+                stats(2_039, 351, 1, 91, 0, 3, 0, 0, 0),
+                stats(97_862, 17_102, 45, 3_162, 0, 993, 0, 0, 1),
+                stats(4_085_603, 757_163, 1_929, 128_013, 15_172, 25_523, 42, 6, 43),
+            ]),
+            (&ending, vec![
+                stats(14, 1, 0, 0, 0, 2, 0, 0, 0),
+                stats(191, 14, 0, 0, 0, 10, 0, 0, 0),
+                stats(2_812, 209, 2, 0, 0, 267, 3, 0, 0),
+                stats(43_238, 3_348, 123, 0, 0, 1_680, 106, 0, 17),
+                stats(674_624, 52_051, 1_165, 0, 0, 52_950, 1_292, 3, 0),
+            ]),
+            (&promotions, vec![
+                stats(6, 0, 0, 0, 0, 0, 0, 0, 0),
+                stats(264, 87, 0, 6, 48, 10, 0, 0, 0),
+                stats(9_467, 1_021, 4, 0, 120, 38, 2, 0, 22),
+                stats(422_333, 131_393, 0, 7_795, 60_032, 15_492, 19, 0, 5),
+            ]),
+        ] {
+            for (depth, expected) in (1..).zip(expected) {
+                let report = perft_parallel(position, depth, 0, None);
+                assert_eq!(report.total, expected, "{} at depth {}", position.to_fen(), depth);
+                assert_eq!(report.divide.iter().map(|&(_, stats)| stats).sum::<PerftStats>(), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::moves::Move;
+use crate::position::{create_default_position, try_create_position_from_fen, Position};
+use crate::Error;
+
+pub mod reader;
+pub mod writer;
+
+pub use reader::PgnReader;
+pub use writer::write_pgn;
+
+// The seven tags every exported game starts with, in this order
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown, // still going or never finished
+}
+
+impl GameResult {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<GameResult, ()> {
+        match text {
+            "1-0" => Ok(GameResult::WhiteWins),
+            "0-1" => Ok(GameResult::BlackWins),
+            "1/2-1/2" => Ok(GameResult::Draw),
+            "*" => Ok(GameResult::Unknown),
+            _ => Err(()),
+        }
+    }
+}
+
+// One move of a line, with whatever was written around it
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Node {
+    pub move_: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub starting_comment: Option<String>, // a comment before the first move of a line
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<Node>>, // lines played instead of this move
+}
+
+impl Node {
+    pub fn new(move_: Move, san: String) -> Node {
+        Node { move_, san, nags: Vec::new(), starting_comment: None, comment: None, variations: Vec::new() }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Game {
+    pub tags: Vec<(String, String)>, // in the order they were read
+    pub moves: Vec<Node>, // the main line
+    pub result: GameResult,
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game { tags: Vec::new(), moves: Vec::new(), result: GameResult::Unknown }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    // Replaces the tag if it's already there, otherwise adds it at the end
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // The FEN tag if there is one, otherwise the normal starting position
+    pub fn starting_position(&self) -> Result<Position, Error> {
+        match self.tag("FEN") {
+            Some(fen) => try_create_position_from_fen(fen),
+            None => Ok(create_default_position()),
+        }
+    }
+
+    pub fn mainline(&self) -> impl Iterator<Item = Move> + '_ {
+        self.moves.iter().map(|node| node.move_)
+    }
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
@@ -0,0 +1,483 @@
+use std::io::BufRead;
+use crate::pgn::{Game, GameResult, Node};
+use crate::position::Position;
+use crate::Error;
+
+// Reads games one at a time, so a whole collection never has to be in memory
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    pending: Option<String>, // the first line of the next game, read while looking for the end of this one
+    leftover: Option<String>, // movetext found after the last game's result, the start of another game
+    games: usize,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader { reader, pending: None, leftover: None, games: 0 }
+    }
+
+    // Everything up to the next game's tags, None once the input is used up
+    fn read_game_text(&mut self) -> Option<Result<String, String>> {
+        let mut text = self.leftover.take().unwrap_or_default();
+        let mut in_movetext = !text.trim().is_empty();
+        let mut in_comment = false;
+
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => {
+                    let mut line = String::new();
+                    match self.reader.read_line(&mut line) {
+                        Ok(0) => break,
+                        Ok(_) => line,
+                        Err(err) => return Some(Err(err.to_string())),
+                    }
+                }
+            };
+
+            let trimmed = line.trim();
+            if !in_comment {
+                if line.starts_with('%') {
+                    continue; // escaped line, meant for other programs
+                }
+                if trimmed.starts_with('[') {
+                    if in_movetext {
+                        self.pending = Some(line);
+                        break;
+                    }
+                    text.push_str(&line);
+                    continue;
+                }
+                if !trimmed.is_empty() {
+                    in_movetext = true;
+                }
+            }
+
+            for c in line.chars() {
+                match c {
+                    '}' if in_comment => in_comment = false,
+                    '{' if !in_comment => in_comment = true,
+                    ';' if !in_comment => break, // the rest of the line is a comment
+                    _ => {}
+                }
+            }
+            text.push_str(&line);
+            if !line.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+
+        if text.trim().is_empty() { None } else { Some(Ok(text)) }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, Error>;
+
+    // A bad game is reported and skipped, the games after it are still read
+    fn next(&mut self) -> Option<Result<Game, Error>> {
+        let text = self.read_game_text()?;
+        self.games += 1;
+        let game = self.games;
+        Some(text.map_err(|message| Error::InvalidPgn { game, ply: 0, message }).and_then(|text| {
+            let mut parser = Parser::new(&text, game);
+            let game = parser.parse_game()?;
+            self.leftover = parser.rest();
+            Ok(game)
+        }))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Nag(u8),
+    Result(GameResult),
+    San(String),
+}
+
+// The short forms of the first six NAGs
+const SUFFIXES: [(&str, u8); 6] = [("!", 1), ("?", 2), ("!!", 3), ("??", 4), ("!?", 5), ("?!", 6)];
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    peeked: Option<Token>,
+    game: usize,
+    result: Option<GameResult>,
+}
+
+impl Parser {
+    fn new(text: &str, game: usize) -> Parser {
+        Parser { chars: text.chars().collect(), index: 0, peeked: None, game, result: None }
+    }
+
+    fn error(&self, position: &Position, message: String) -> Error {
+        Error::InvalidPgn { game: self.game, ply: position.history().len() + 1, message }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn take_while(&mut self, keep: impl Fn(char) -> bool) -> String {
+        let start = self.index;
+        while self.peek_char().is_some_and(&keep) {
+            self.index += 1;
+        }
+        self.chars[start..self.index].iter().collect()
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, String> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.lex(),
+        }
+    }
+
+    fn lex(&mut self) -> Result<Option<Token>, String> {
+        loop {
+            self.take_while(char::is_whitespace);
+            let Some(c) = self.peek_char() else {
+                return Ok(None);
+            };
+            self.index += 1;
+
+            return Ok(Some(match c {
+                '[' => self.lex_tag()?,
+                '{' => {
+                    let comment = self.take_while(|c| c != '}');
+                    if self.peek_char().is_none() {
+                        return Err("unterminated comment".to_string());
+                    }
+                    self.index += 1;
+                    // Line breaks inside are only wrapping
+                    Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                ';' => Token::Comment(self.take_while(|c| c != '\n').trim().to_string()),
+                '(' => Token::VariationStart,
+                ')' => Token::VariationEnd,
+                '*' => Token::Result(GameResult::Unknown),
+                '$' => {
+                    let nag = self.take_while(|c| c.is_ascii_digit());
+                    Token::Nag(nag.parse().map_err(|_| format!("invalid NAG \"${}\"", nag))?)
+                }
+                '!' | '?' => {
+                    self.index -= 1;
+                    self.lex_suffix()?
+                }
+                c if c.is_ascii_alphanumeric() => {
+                    self.index -= 1;
+                    let mut symbol = self.take_while(|c| c.is_ascii_alphanumeric() || "+#=:-/_".contains(c));
+                    if symbol.chars().all(|c| c.is_ascii_digit()) && self.peek_char() == Some('.') {
+                        self.take_while(|c| c == '.');
+                        continue; // move number
+                    }
+                    match symbol.parse() {
+                        Ok(result) => Token::Result(result),
+                        Err(_) => {
+                            self.lex_en_passant(&mut symbol);
+                            Token::San(symbol)
+                        }
+                    }
+                }
+                c => return Err(format!("unexpected character '{}'", c)),
+            }));
+        }
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(offset, c)| self.chars.get(self.index + offset) == Some(&c))
+    }
+
+    // dxc6 e.p. is one move, with or without the space
+    fn lex_en_passant(&mut self, symbol: &mut String) {
+        if symbol.ends_with('e') && self.starts_with(".p.") {
+            self.index += 3;
+            symbol.push_str(".p.");
+            return;
+        }
+        let start = self.index;
+        self.take_while(|c| c == ' ' || c == '\t');
+        if self.starts_with("e.p.") {
+            self.index += 4;
+            symbol.push_str(" e.p.");
+        } else {
+            self.index = start;
+        }
+    }
+
+    fn lex_suffix(&mut self) -> Result<Token, String> {
+        let suffix = self.take_while(|c| c == '!' || c == '?');
+        SUFFIXES.iter().find(|(text, _)| *text == suffix)
+            .map(|&(_, nag)| Token::Nag(nag))
+            .ok_or_else(|| format!("invalid move suffix \"{}\"", suffix))
+    }
+
+    fn lex_tag(&mut self) -> Result<Token, String> {
+        self.take_while(char::is_whitespace);
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        self.take_while(char::is_whitespace);
+        if name.is_empty() || self.peek_char() != Some('"') {
+            return Err("invalid tag".to_string());
+        }
+        self.index += 1;
+
+        let mut value = String::new();
+        loop {
+            match self.peek_char() {
+                Some('"') => break,
+                Some('\\') => {
+                    self.index += 1;
+                    value.extend(self.peek_char());
+                }
+                Some('\n') | None => return Err(format!("unterminated value for tag {}", name)),
+                Some(c) => value.push(c),
+            }
+            self.index += 1;
+        }
+        self.index += 1;
+
+        self.take_while(char::is_whitespace);
+        if self.peek_char() != Some(']') {
+            return Err(format!("missing ] after tag {}", name));
+        }
+        self.index += 1;
+        Ok(Token::Tag(name, value))
+    }
+
+    // Text after the result that isn't just comments, None if the game used everything up
+    fn rest(&mut self) -> Option<String> {
+        loop {
+            self.take_while(char::is_whitespace);
+            let start = self.index;
+            match self.lex() {
+                Ok(Some(Token::Comment(_))) => continue,
+                Ok(None) => return None,
+                _ => return Some(self.chars[start..].iter().collect()),
+            }
+        }
+    }
+
+    fn parse_game(&mut self) -> Result<Game, Error> {
+        let mut game = Game::new();
+        loop {
+            match self.peek() {
+                Ok(Some(Token::Tag(_, _))) => {
+                    if let Ok(Some(Token::Tag(name, value))) = self.next_token() {
+                        game.tags.push((name, value));
+                    }
+                }
+                Ok(_) => break,
+                Err(message) => return Err(Error::InvalidPgn { game: self.game, ply: 0, message }),
+            }
+        }
+
+        let mut position = game.starting_position()
+            .map_err(|err| Error::InvalidPgn { game: self.game, ply: 0, message: format!("bad FEN tag, {}", err) })?;
+        game.moves = self.parse_line(&mut position, 0)?;
+        game.result = self.result
+            .or_else(|| game.tag("Result").and_then(|result| result.parse().ok()))
+            .unwrap_or(GameResult::Unknown);
+
+        Ok(game)
+    }
+
+    // Reads moves until the end of the variation (or game), leaving position as it was
+    fn parse_line(&mut self, position: &mut Position, depth: usize) -> Result<Vec<Node>, Error> {
+        let mut line: Vec<Node> = Vec::new();
+        let mut starting_comment: Option<String> = None;
+
+        loop {
+            let token = self.next_token().map_err(|message| self.error(position, message))?;
+            match token {
+                None if depth > 0 => return Err(self.error(position, "unterminated variation".to_string())),
+                None => break,
+                Some(Token::San(text)) => {
+                    let move_ = position.parse_san(&text).map_err(|err| self.error(position, err.to_string()))?;
+                    let mut node = Node::new(move_, position.san(move_));
+                    if line.is_empty() {
+                        node.starting_comment = starting_comment.take();
+                    }
+                    line.push(node);
+                    position.push(move_);
+                }
+                Some(Token::Nag(nag)) => match line.last_mut() {
+                    Some(node) => node.nags.push(nag),
+                    None => return Err(self.error(position, format!("NAG ${} before any move", nag))),
+                },
+                Some(Token::Comment(comment)) => {
+                    let target = match line.last_mut() {
+                        Some(node) => &mut node.comment,
+                        None => &mut starting_comment,
+                    };
+                    *target = Some(match target.take() {
+                        Some(before) => format!("{} {}", before, comment),
+                        None => comment,
+                    });
+                }
+                Some(Token::VariationStart) => {
+                    let Some(node) = line.last() else {
+                        return Err(self.error(position, "variation before any move".to_string()));
+                    };
+                    let move_ = node.move_;
+                    position.pop();
+                    let variation = self.parse_line(position, depth + 1)?;
+                    position.push(move_);
+                    line.last_mut().unwrap().variations.push(variation);
+                }
+                Some(Token::VariationEnd) if depth > 0 => break,
+                Some(Token::VariationEnd) => return Err(self.error(position, "unmatched )".to_string())),
+                Some(Token::Result(_)) if depth > 0 => return Err(self.error(position, "result inside a variation".to_string())),
+                Some(Token::Result(result)) => {
+                    self.result = Some(result);
+                    break;
+                }
+                Some(Token::Tag(name, _)) => return Err(self.error(position, format!("tag {} inside the moves", name))),
+            }
+        }
+
+        for _ in &line {
+            position.pop();
+        }
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::{GameResult, PgnReader};
+    use crate::position::create_position_from_fen;
+    use crate::Error;
+
+    const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+% an escaped line
+[Event "Variations"]
+[Result "*"]
+
+{Before the first move} 1. e4 $1 (1. d4 d5 (1... Nf6 2. c4) 2. c4 {Queen's gambit}) 1... e5!? ; rest of line
+2. Nf3 Nc6?! (2... d6 3. d4) 3. Bc4 *
+
+[Event "Odds"]
+[SetUp "1"]
+[FEN "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQK2R w KQkq - 0 1"]
+
+1.e4 e5 2.0-0?? 0-1
+"#;
+
+    #[test]
+    fn test_read_games() {
+        let games: Vec<_> = PgnReader::new(GAMES.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(games.len(), 3);
+
+        let fischer = &games[0];
+        assert_eq!(fischer.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(fischer.tags.len(), 7);
+        assert_eq!(fischer.moves.len(), 85);
+        assert_eq!(fischer.result, GameResult::Draw);
+        assert_eq!(fischer.moves[4].comment.as_deref(), Some("This opening is called the Ruy Lopez."));
+        assert_eq!(fischer.moves[84].san, "Re6");
+
+        let variations = &games[1];
+        assert_eq!(variations.result, GameResult::Unknown);
+        let e4 = &variations.moves[0];
+        assert_eq!(e4.starting_comment.as_deref(), Some("Before the first move"));
+        assert_eq!(e4.nags, vec![1]);
+        assert_eq!(e4.variations.len(), 1);
+        let d4 = &e4.variations[0];
+        assert_eq!(d4.iter().map(|node| node.san.as_str()).collect::<Vec<_>>(), ["d4", "d5", "c4"]);
+        assert_eq!(d4[1].variations[0].iter().map(|node| node.san.as_str()).collect::<Vec<_>>(), ["Nf6", "c4"]);
+        assert_eq!(d4[2].comment.as_deref(), Some("Queen's gambit"));
+        assert_eq!(variations.moves[1].nags, vec![5]);
+        assert_eq!(variations.moves[1].comment.as_deref(), Some("rest of line"));
+        assert_eq!(variations.moves[3].nags, vec![6]);
+        assert_eq!(variations.moves[3].variations[0].len(), 2);
+        assert_eq!(variations.mainline().count(), 5);
+
+        let odds = &games[2];
+        assert_eq!(odds.result, GameResult::BlackWins);
+        assert_eq!(odds.moves[2].san, "O-O");
+        assert_eq!(odds.moves[2].nags, vec![4]);
+        let mut position = odds.starting_position().unwrap();
+        for move_ in odds.mainline() {
+            position.push(move_);
+        }
+        assert_eq!(position.to_fen(), create_position_from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQ1RK1 b kq - 1 2").to_fen());
+
+        let text = "1. e4 d5 2. e5 f5 3. exf6 e.p. Nxf6 4. c4 a6 5. c5 b5 6. cxb6e.p. *";
+        let passant = PgnReader::new(text.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(passant.moves.len(), 11);
+        assert!(passant.moves[4].move_.is_en_passant() && passant.moves[10].move_.is_en_passant());
+        assert_eq!(passant.moves[4].san, "exf6");
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let text = "[Event \"Fine\"]\n\n1. e4 e5 *\n\n[Event \"Illegal\"]\n\n1. e4 e5 2. Ke3 *\n\n\
+            [Event \"Unclosed\"]\n\n1. d4 (1. e4 *\n\n[Event \"Fine again\"]\n\n1. c4 1-0\n";
+        let games: Vec<_> = PgnReader::new(text.as_bytes()).collect();
+        assert_eq!(games.len(), 4);
+        assert!(games[0].is_ok());
+        assert!(matches!(&games[1], Err(Error::InvalidPgn { game: 2, ply: 3, .. })), "{:?}", games[1]);
+        assert!(matches!(&games[2], Err(Error::InvalidPgn { game: 3, ply: 2, .. })), "{:?}", games[2]);
+        assert_eq!(games[3].as_ref().unwrap().result, GameResult::WhiteWins);
+
+        // A result ends the game even without tags before the next one
+        let games: Vec<_> = PgnReader::new("1. e4 e5 *\n1. d4 d5 1-0 {done}\n".as_bytes()).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().unwrap().moves.len(), 2);
+        let second = games[1].as_ref().unwrap();
+        assert_eq!((second.mainline().count(), second.result), (2, GameResult::WhiteWins));
+        assert_eq!(second.moves[0].san, "d4");
+
+        // So moves after it are not quietly dropped
+        let games: Vec<_> = PgnReader::new("1. e4 e5 1-0 2. Nf3 Ke7 *".as_bytes()).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().unwrap().mainline().count(), 2);
+        assert!(matches!(&games[1], Err(Error::InvalidPgn { game: 2, ply: 2, .. })), "{:?}", games[1]);
+
+        for (text, ply) in [
+            ("1. e4 {never closed", 2),
+            ("[Event \"x]\n1. e4 *", 0),
+            ("1. e4 $x *", 2),
+            ("1. e4 ) *", 2),
+            ("$1 1. e4 *", 1),
+            ("1. e4 e5 2. Nf3 (1-0) *", 3),
+            // A setup the move generator can't play is an error too, not a panic
+            ("[FEN \"r3k3/8/8/8/8/8/8/8 w - - 0 1\"]\n\n1. Kd1 *\n", 0),
+            ("[FEN \"4k3/8/8/8/8/8/8/4R1K1 w - - 0 1\"]\n\n1. Rxe8 *\n", 0),
+        ] {
+            let result = PgnReader::new(text.as_bytes()).next().unwrap();
+            match result {
+                Err(Error::InvalidPgn { game: 1, ply: found, .. }) => assert_eq!(found, ply, "{}", text),
+                other => panic!("{} gave {:?}", text, other),
+            }
+        }
+    }
+}
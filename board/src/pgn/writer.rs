@@ -0,0 +1,217 @@
+use std::io;
+use crate::pgn::{Game, Node, SEVEN_TAG_ROSTER};
+use crate::position::{Position, DEFAULT_FEN};
+use crate::types::Color;
+use crate::Error;
+
+// Export format keeps movetext lines at most this long
+pub const LINE_WIDTH: usize = 80;
+
+impl Game {
+    // Sets the FEN and SetUp tags for a game that doesn't start from the normal position
+    pub fn set_starting_position(&mut self, position: &Position) {
+        self.tags.retain(|(name, _)| name != "FEN" && name != "SetUp");
+        let fen = position.to_fen();
+        if fen != DEFAULT_FEN {
+            self.set_tag("SetUp", "1");
+            self.set_tag("FEN", &fen);
+        }
+    }
+
+    // The whole game in export format, ending with an empty line
+    pub fn to_pgn(&self) -> Result<String, Error> {
+        let mut pgn = String::new();
+
+        // Seven tag roster first, then everything else as it was
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.as_str(),
+                "Date" => self.tag(name).unwrap_or("????.??.??"),
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            pgn.push_str(&tag_line(name, value));
+        }
+        let setup = self.tag("FEN").is_some() && self.tag("SetUp").is_none();
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                if name == "FEN" && setup {
+                    pgn.push_str(&tag_line("SetUp", "1"));
+                }
+                pgn.push_str(&tag_line(name, value));
+            }
+        }
+        pgn.push('\n');
+
+        let mut tokens = Tokens::default();
+        let mut position = self.starting_position()?;
+        write_line(&mut tokens, &mut position, &self.moves);
+        tokens.push(self.result.as_str());
+
+        // Fill each line up to the width
+        let mut line = String::new();
+        for token in tokens.list {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push_str("\n\n");
+
+        Ok(pgn)
+    }
+}
+
+pub fn write_pgn(game: &Game, out: &mut impl io::Write) -> io::Result<()> {
+    let pgn = game.to_pgn().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    out.write_all(pgn.as_bytes())
+}
+
+fn tag_line(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Default)]
+struct Tokens {
+    list: Vec<String>,
+    open: bool, // the next token starts a variation
+}
+
+impl Tokens {
+    fn push(&mut self, token: &str) {
+        if self.open {
+            self.list.push(format!("({}", token));
+            self.open = false;
+        } else {
+            self.list.push(token.to_string());
+        }
+    }
+
+    // Comments are split on spaces so they can wrap like everything else
+    fn push_comment(&mut self, comment: &str) {
+        let words: Vec<&str> = comment.split_whitespace().collect();
+        if words.is_empty() {
+            self.push("{}");
+            return;
+        }
+        for (index, word) in words.iter().enumerate() {
+            let open = if index == 0 { "{" } else { "" };
+            let close = if index == words.len() - 1 { "}" } else { "" };
+            self.push(&format!("{}{}{}", open, word, close));
+        }
+    }
+
+    fn close(&mut self) {
+        if self.open {
+            self.push("");
+        }
+        if let Some(last) = self.list.last_mut() {
+            last.push(')');
+        }
+    }
+}
+
+// Adds the line played from position, leaving position as it was
+fn write_line(tokens: &mut Tokens, position: &mut Position, line: &[Node]) {
+    let mut need_number = true; // black's moves get a number at the start of a line and after comments or variations
+    for node in line {
+        if let Some(comment) = &node.starting_comment {
+            tokens.push_comment(comment);
+        }
+
+        if position.side_to_move == Color::White {
+            tokens.push(&format!("{}.", position.fullmove_number));
+        } else if need_number {
+            tokens.push(&format!("{}...", position.fullmove_number));
+        }
+        tokens.push(&position.san(node.move_));
+        for nag in &node.nags {
+            tokens.push(&format!("${}", nag));
+        }
+        need_number = false;
+
+        if let Some(comment) = &node.comment {
+            tokens.push_comment(comment);
+            need_number = true;
+        }
+        for variation in &node.variations {
+            tokens.open = true;
+            write_line(tokens, position, variation);
+            tokens.close();
+            need_number = true;
+        }
+
+        position.push(node.move_);
+    }
+
+    for _ in line {
+        position.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::writer::LINE_WIDTH;
+    use crate::pgn::{Game, GameResult, Node, PgnReader};
+    use crate::position::{create_default_position, create_position_from_fen};
+
+    fn read(text: &str) -> Vec<Game> {
+        PgnReader::new(text.as_bytes()).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_write_variations() {
+        let game = &read("[Event \"Test \\\"quoted\\\"\"]\n[ECO \"C50\"]\n\n\
+            {Start} 1. e4 $1 (1. d4 d5 (1... Nf6 2. c4) 2. c4 {Queen's gambit}) e5 2. Nf3 Nc6 (2... d6) 3. Bc4 1-0")[0];
+        assert_eq!(game.to_pgn().unwrap(), "[Event \"Test \\\"quoted\\\"\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+            [White \"?\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[ECO \"C50\"]\n\n\
+            {Start} 1. e4 $1 (1. d4 d5 (1... Nf6 2. c4) 2. c4 {Queen's gambit}) 1... e5 2.\n\
+            Nf3 Nc6 (2... d6) 3. Bc4 1-0\n\n");
+    }
+
+    #[test]
+    fn test_write_setup() {
+        let mut game = Game::new();
+        let mut position = create_position_from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 12");
+        game.set_starting_position(&position);
+        for uci in ["e8d7", "e2e4", "d7e6"] {
+            let move_ = position.parse_uci_move(uci).unwrap();
+            game.moves.push(Node::new(move_, position.san(move_)));
+            position.push(move_);
+        }
+        game.result = GameResult::Draw;
+
+        let pgn = game.to_pgn().unwrap();
+        assert!(pgn.contains("[Result \"1/2-1/2\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n\n"));
+        assert!(pgn.ends_with("\n\n12... Kd7 13. e4 Ke6 1/2-1/2\n\n"));
+        let again = &read(&pgn)[0];
+        assert_eq!((&again.moves, again.result), (&game.moves, game.result));
+
+        // Back to the normal start drops them again
+        game.set_starting_position(&create_default_position());
+        assert!(game.tag("FEN").is_none() && game.tag("SetUp").is_none());
+    }
+
+    #[test]
+    fn test_write_wraps_and_round_trips() {
+        let text = "[Event \"Long\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez and this comment is long \
+            enough that it has to be wrapped over more than one line} 3... a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 \
+            8. c3 O-O 9. h3 Nb8 10. d4 Nbd7 11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 \
+            17. dxe5 Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6 23. Ne5 Rae8 \
+            24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 1/2-1/2";
+        let game = &read(text)[0];
+        let pgn = game.to_pgn().unwrap();
+        assert!(pgn.lines().all(|line| line.len() <= LINE_WIDTH), "{}", pgn);
+        assert!(pgn.lines().filter(|line| !line.starts_with('[')).count() > 4);
+
+        let again = &read(&pgn)[0];
+        assert_eq!(again.moves, game.moves);
+        assert_eq!(again.result, game.result);
+        assert_eq!(again.to_pgn().unwrap(), pgn);
+    }
+}
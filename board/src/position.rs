@@ -0,0 +1,547 @@
+use crate::board::{Board, try_create_board_from_string};
+use crate::moves::{find_piece_type, get_moves, get_moves_into, get_moves_of_type_into, get_pseudo_legal_moves_into, in_check, is_legal, is_pseudo_legal, GenType, Move, MoveList, Undo};
+use crate::types::{CastleRights, Color, File, Piece, PieceKind, Promotion, Rank, Square};
+use crate::Error;
+use crate::zobrist;
+
+pub const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// What push needs to remember so pop can take the move back
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HistoryEntry {
+    pub move_: Move,
+    pub undo: Undo,
+    pub halfmove_clock: u32,
+    pub hash: u64, // key of the position before the move
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Checkmate(Color), // the winner
+    Stalemate,
+    InsufficientMaterial,
+    SeventyFiveMoves,
+    FivefoldRepetition,
+    // Draws a player can claim, the game only ends if they do
+    FiftyMoves,
+    ThreefoldRepetition,
+}
+
+impl Outcome {
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            Outcome::Checkmate(color) => Some(color),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Position {
+    pub board: Board,
+    pub side_to_move: Color,
+    pub castle_rights: CastleRights,
+    pub en_passant: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    history: Vec<HistoryEntry>,
+}
+
+pub fn create_position_from_fen(fen: &str) -> Position {
+    try_create_position_from_fen(fen).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub fn try_create_position_from_fen(fen: &str) -> Result<Position, Error> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    // The clocks are optional, some tools only emit the first four fields
+    if fields.len() < 4 || fields.len() > 6 {
+        return Err(Error::InvalidFieldCount(fields.len()));
+    }
+
+    let board = try_create_board_from_string(fields[0])?;
+
+    let side_to_move = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => { return Err(Error::InvalidSideToMove(fields[1].to_string())); }
+    };
+
+    let castle_rights = fields[2].parse()?;
+
+    let en_passant = if fields[3] == "-" {
+        None
+    } else {
+        let square: Square = fields[3].parse()
+            .map_err(|_| Error::InvalidEnPassant(fields[3].to_string()))?;
+        // The square behind a pawn that just moved two, so it depends on who is moving
+        if square.rank() != Rank::Sixth.relative(side_to_move) {
+            return Err(Error::InvalidEnPassant(fields[3].to_string()));
+        }
+        // The pawn stands in front of it, and the squares it crossed are empty
+        let forward = if side_to_move == Color::White { -1 } else { 1 };
+        let pawn = square.offset(0, forward).and_then(|pawn| board.piece_at(pawn));
+        let behind = square.offset(0, -forward).and_then(|behind| board.piece_at(behind));
+        if board.piece_at(square).is_some() || behind.is_some() ||
+            pawn != Some(Piece::new(!side_to_move, PieceKind::Pawn)) {
+            return Err(Error::InvalidEnPassant(fields[3].to_string()));
+        }
+        Some(square)
+    };
+
+    let halfmove_clock = match fields.get(4) {
+        Some(field) => field.parse().map_err(|_| Error::InvalidHalfmoveClock(field.to_string()))?,
+        None => 0,
+    };
+    let fullmove_number = match fields.get(5) {
+        Some(field) => match field.parse() {
+            Ok(number) if number > 0 => number,
+            _ => { return Err(Error::InvalidFullmoveNumber(field.to_string())); }
+        },
+        None => 1,
+    };
+
+    // Anything else can't be played, the move generator needs both kings and a king can't be taken
+    for color in [Color::White, Color::Black] {
+        let kings = board.piece_bitboard(Piece::new(color, PieceKind::King)).count_ones();
+        if kings != 1 {
+            return Err(Error::InvalidKingCount(color, kings));
+        }
+    }
+    if board.checkers(!side_to_move) != 0 {
+        return Err(Error::OpponentInCheck);
+    }
+
+    Ok(Position {
+        board,
+        side_to_move,
+        castle_rights,
+        en_passant,
+        halfmove_clock,
+        fullmove_number,
+        history: Vec::new(),
+    })
+}
+
+pub fn create_default_position() -> Position {
+    create_position_from_fen(DEFAULT_FEN)
+}
+
+impl Position {
+    pub fn hash(&self) -> u64 {
+        zobrist::hash(&self.board, self.side_to_move, self.castle_rights, self.en_passant)
+    }
+
+    // Moves played since the position was created, oldest first
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    pub fn push(&mut self, move_: Move) {
+        let entry_clock = self.halfmove_clock;
+        let hash = self.hash();
+        if move_.is_capture() || find_piece_type(&self.board, move_.from()).kind == PieceKind::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        let undo = move_.make_move(&mut self.board, &mut self.castle_rights, &mut self.en_passant);
+        if self.side_to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = !self.side_to_move;
+
+        self.history.push(HistoryEntry {
+            move_,
+            undo,
+            halfmove_clock: entry_clock,
+            hash,
+        });
+    }
+
+    // Takes back the last pushed move, None if there is nothing to take back
+    pub fn pop(&mut self) -> Option<Move> {
+        let entry = self.history.pop()?;
+
+        self.side_to_move = !self.side_to_move;
+        if self.side_to_move == Color::Black {
+            self.fullmove_number -= 1;
+        }
+        entry.move_.unmake_move(&mut self.board, &mut self.castle_rights, &mut self.en_passant, entry.undo);
+        self.halfmove_clock = entry.halfmove_clock;
+
+        Some(entry.move_)
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        get_moves(&self.board, self.en_passant, self.castle_rights, self.side_to_move)
+    }
+
+    pub fn legal_moves_into(&self, moves: &mut MoveList) {
+        get_moves_into(&self.board, self.en_passant, self.castle_rights, self.side_to_move, moves)
+    }
+
+    pub fn moves_of_type_into(&self, gen_type: GenType, moves: &mut MoveList) {
+        get_moves_of_type_into(&self.board, self.en_passant, self.castle_rights, self.side_to_move, gen_type, moves)
+    }
+
+    // Moves that still need is_legal, the king may be left in check
+    pub fn pseudo_legal_moves_into(&self, moves: &mut MoveList) {
+        get_pseudo_legal_moves_into(&self.board, self.en_passant, self.castle_rights, self.side_to_move, moves)
+    }
+
+    // For moves from somewhere else, like the hash table or killers
+    pub fn is_pseudo_legal(&self, move_: Move) -> bool {
+        is_pseudo_legal(&self.board, self.en_passant, self.castle_rights, self.side_to_move, move_)
+    }
+
+    // Only meaningful for a move that is_pseudo_legal
+    pub fn is_legal(&self, move_: Move) -> bool {
+        is_legal(&self.board, self.side_to_move, move_)
+    }
+
+    pub fn in_check(&self) -> bool {
+        in_check(&self.board, self.side_to_move)
+    }
+
+    // Pieces giving check to the side to move
+    pub fn checkers(&self) -> u64 {
+        self.board.checkers(self.side_to_move)
+    }
+
+    // How many times the current position has been on the board, counting this one
+    pub fn repetitions(&self) -> usize {
+        let hash = self.hash();
+        // Nothing before the last capture or pawn move can repeat, and only every other ply has the same side to move
+        let reversible = (self.halfmove_clock as usize).min(self.history.len());
+        1 + self.history[self.history.len() - reversible..].iter().rev()
+            .skip(1)
+            .step_by(2)
+            .filter(|entry| entry.hash == hash)
+            .count()
+    }
+
+    pub fn is_insufficient_material(&self) -> bool {
+        let board = &self.board;
+        if board.pieces(PieceKind::Pawn) | board.pieces(PieceKind::Rook) | board.pieces(PieceKind::Queen) != 0 {
+            return false;
+        }
+
+        let knights = board.pieces(PieceKind::Knight);
+        let bishops = board.pieces(PieceKind::Bishop);
+        if (knights | bishops).count_ones() <= 1 {
+            return true; // a lone minor piece can't mate
+        }
+
+        // Any number of bishops that all stand on the same colour can't mate either
+        const DARK_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0)
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        let mut moves = MoveList::new();
+        self.legal_moves_into(&mut moves);
+        if moves.is_empty() {
+            return Some(if self.in_check() { Outcome::Checkmate(!self.side_to_move) } else { Outcome::Stalemate });
+        }
+        if self.is_insufficient_material() {
+            return Some(Outcome::InsufficientMaterial);
+        }
+        if self.halfmove_clock >= 150 {
+            return Some(Outcome::SeventyFiveMoves);
+        }
+
+        let repetitions = self.repetitions();
+        if repetitions >= 5 {
+            Some(Outcome::FivefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(Outcome::FiftyMoves)
+        } else if repetitions >= 3 {
+            Some(Outcome::ThreefoldRepetition)
+        } else {
+            None
+        }
+    }
+
+    // Finds the legal move written in long algebraic form, the king taking its own rook also castles
+    pub fn parse_uci_move(&self, text: &str) -> Result<Move, Error> {
+        let invalid = || Error::InvalidUciMove(text.to_string());
+        if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+            return Err(invalid());
+        }
+        let from: Square = text[0..2].parse().map_err(|_| invalid())?;
+        let mut to: Square = text[2..4].parse().map_err(|_| invalid())?;
+        let promotion = match text[4..].chars().next() {
+            Some(c) => Some(Promotion::from_char(c).ok_or_else(invalid)?),
+            None => None,
+        };
+
+        let own = self.board.colored(self.side_to_move);
+        if self.board.pieces(PieceKind::King) & own & from.bit() != 0 &&
+            self.board.pieces(PieceKind::Rook) & own & to.bit() != 0 && from.rank() == to.rank() {
+            to = Square::new(if to.file() > from.file() { File::G } else { File::C }, from.rank());
+        }
+
+        self.legal_moves().into_iter()
+            .find(|move_| move_.from() == from && move_.to() == to && move_.promotion() == promotion)
+            .ok_or_else(|| Error::IllegalMove(text.to_string()))
+    }
+
+    pub fn to_fen(&self) -> String {
+        format!("{} {} {} {} {} {}",
+                self.board.to_fen(),
+                if self.side_to_move == Color::White { 'w' } else { 'b' },
+                self.castle_rights,
+                self.en_passant.map_or("-".to_string(), |square| square.to_string()),
+                self.halfmove_clock,
+                self.fullmove_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{square_from_algebraic, try_square_from_algebraic};
+    use crate::moves::{try_find_piece_type, Move};
+    use crate::position::{create_default_position, create_position_from_fen, try_create_position_from_fen, Outcome};
+    use crate::types::{CastleRights, Color, Promotion, Square};
+    use crate::Error;
+
+    #[test]
+    fn test_default_position() {
+        let position = create_default_position();
+        assert_eq!(position.side_to_move, Color::White);
+        assert_eq!(position.castle_rights, CastleRights::ALL);
+        assert_eq!(position.en_passant, None);
+        assert_eq!(position.halfmove_clock, 0);
+        assert_eq!(position.fullmove_number, 1);
+        assert_eq!(position.board.all_pieces(), 0xFFFF00000000FFFF);
+    }
+
+    #[test]
+    fn test_full_fen() {
+        let position = create_position_from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kkq f6 0 3");
+        assert_eq!(position.side_to_move, Color::White);
+        assert_eq!(position.castle_rights, CastleRights::WHITE_KING_SIDE | CastleRights::BLACK);
+        assert_eq!(position.en_passant, Some(square_from_algebraic("f6")));
+        assert_eq!(position.halfmove_clock, 0);
+        assert_eq!(position.fullmove_number, 3);
+
+        let position = create_position_from_fen("8/8/8/8/8/8/8/K1k5 b - - 37 102");
+        assert_eq!(position.side_to_move, Color::Black);
+        assert_eq!(position.castle_rights, CastleRights::NONE);
+        assert_eq!(position.en_passant, None);
+        assert_eq!(position.halfmove_clock, 37);
+        assert_eq!(position.fullmove_number, 102);
+    }
+
+    #[test]
+    fn test_missing_clocks() {
+        let position = create_position_from_fen("8/8/8/8/8/8/8/K1k5 w - -");
+        assert_eq!(position.halfmove_clock, 0);
+        assert_eq!(position.fullmove_number, 1);
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kkq f6 0 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ] {
+            assert_eq!(create_position_from_fen(fen).to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn test_fen_missing_clocks() {
+        assert_eq!(create_position_from_fen("8/8/8/8/8/8/8/K1k5 w - -").to_fen(), "8/8/8/8/8/8/8/K1k5 w - - 0 1");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_side() {
+        create_position_from_fen("8/8/8/8/8/8/8/K1k5 x - - 0 1");
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        let cases = [
+            ("8/8/8/8/8/8/K1k5 w - - 0 1", Error::InvalidRankCount(7)),
+            ("8/8/8/8/8/8/8/K1k5/8 w - - 0 1", Error::InvalidRankCount(9)),
+            ("8/8/8/8/8/8/8/K1x5 w - - 0 1", Error::InvalidPiece('x')),
+            ("8/8/8/8/8/8/8/K1k6 w - - 0 1", Error::RankOverfull(1)),
+            ("8/8/8/8/8/8/8/K1k4 w - - 0 1", Error::RankIncomplete(1)),
+            ("8/8/8/8/8/8/8/K1k5 w -", Error::InvalidFieldCount(3)),
+            ("8/8/8/8/8/8/8/K1k5 white - - 0 1", Error::InvalidSideToMove("white".to_string())),
+            ("8/8/8/8/8/8/8/K1k5 w KX - 0 1", Error::InvalidCastling("KX".to_string())),
+            ("8/8/8/8/8/8/8/K1k5 w KK - 0 1", Error::InvalidCastling("KK".to_string())),
+            ("8/8/8/8/8/8/8/K1k5 w - e9 0 1", Error::InvalidEnPassant("e9".to_string())),
+            ("8/8/8/8/8/8/8/K1k5 w - e3 0 1", Error::InvalidEnPassant("e3".to_string())),
+            ("4k3/8/8/3P4/8/8/8/4K3 w - e6 0 1", Error::InvalidEnPassant("e6".to_string())),
+            ("4k3/4p3/8/3Pp3/8/8/8/4K3 w - e6 0 1", Error::InvalidEnPassant("e6".to_string())),
+            ("4k3/8/4n3/3Pp3/8/8/8/4K3 w - e6 0 1", Error::InvalidEnPassant("e6".to_string())),
+            ("4k3/8/8/3PN3/8/8/8/4K3 w - e6 0 1", Error::InvalidEnPassant("e6".to_string())),
+            ("8/8/8/8/8/8/8/K1k5 w - - x 1", Error::InvalidHalfmoveClock("x".to_string())),
+            ("8/8/8/8/8/8/8/K1k5 w - - 0 0", Error::InvalidFullmoveNumber("0".to_string())),
+            ("r3k3/8/8/8/8/8/8/8 w - - 0 1", Error::InvalidKingCount(Color::White, 0)),
+            ("4k3/8/8/8/8/8/8/K3K3 w - - 0 1", Error::InvalidKingCount(Color::White, 2)),
+            ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", Error::OpponentInCheck),
+            ("8/8/8/8/8/8/8/Kk6 w - - 0 1", Error::OpponentInCheck),
+        ];
+
+        for (fen, error) in cases {
+            assert_eq!(try_create_position_from_fen(fen).err(), Some(error), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_square_errors() {
+        assert_eq!(try_square_from_algebraic("e4"), Ok(Square::from_index(28)));
+        assert_eq!(try_square_from_algebraic("i1"), Err(Error::InvalidSquare("i1".to_string())));
+        assert_eq!(try_square_from_algebraic("a0"), Err(Error::InvalidSquare("a0".to_string())));
+        assert_eq!(try_square_from_algebraic("a10"), Err(Error::InvalidSquare("a10".to_string())));
+        assert_eq!(try_square_from_algebraic(""), Err(Error::InvalidSquare("".to_string())));
+        let e4 = Square::from_index(28);
+        assert_eq!(try_find_piece_type(&create_default_position().board, e4), Err(Error::EmptySquare(e4)));
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut position = create_default_position();
+        let mut fens = vec![position.to_fen()];
+        let mut hashes = vec![position.hash()];
+        for uci in ["e2e4", "c7c5", "g1f3", "d7d6", "f1b5", "c8d7", "e1g1", "d7b5", "b1c3", "b5f1"] {
+            let move_ = position.parse_uci_move(uci).unwrap();
+            position.push(move_);
+            fens.push(position.to_fen());
+            hashes.push(position.hash());
+        }
+        assert_eq!(position.to_fen(), "rn1qkbnr/pp2pppp/3p4/2p5/4P3/2N2N2/PPPP1PPP/R1BQ1bK1 w kq - 0 6");
+        assert_eq!(position.history().len(), 10);
+
+        for index in (0..10).rev() {
+            assert_eq!(position.history()[index].hash, hashes[index]);
+            assert!(position.pop().is_some());
+            assert_eq!(position.to_fen(), fens[index]);
+            assert_eq!(position.hash(), hashes[index]);
+        }
+        assert_eq!(position.pop(), None);
+    }
+
+    #[test]
+    fn test_push_clocks() {
+        let mut position = create_position_from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 7 20");
+        position.push(position.parse_uci_move("a8a1").unwrap());
+        assert_eq!(position.to_fen(), "4k2r/8/8/8/8/8/8/r3K2R w Kk - 0 21");
+        position.push(position.parse_uci_move("e1d2").unwrap());
+        assert_eq!(position.to_fen(), "4k2r/8/8/8/8/8/3K4/r6R b k - 1 21");
+        position.pop();
+        position.pop();
+        assert_eq!(position.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 7 20");
+    }
+
+    #[test]
+    fn test_outcome_mate_and_stalemate() {
+        let mut position = create_default_position();
+        for uci in ["f2f3", "e7e5", "g2g4"] {
+            position.push(position.parse_uci_move(uci).unwrap());
+        }
+        assert_eq!(position.outcome(), None);
+        position.push(position.parse_uci_move("d8h4").unwrap());
+        assert_eq!(position.outcome(), Some(Outcome::Checkmate(Color::Black)));
+        assert_eq!(position.outcome().and_then(Outcome::winner), Some(Color::Black));
+
+        assert_eq!(create_position_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").outcome(), Some(Outcome::Stalemate));
+        // Mate still counts when the clock runs out on the same move
+        assert_eq!(create_position_from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 150 90").outcome(), Some(Outcome::Checkmate(Color::White)));
+    }
+
+    #[test]
+    fn test_outcome_move_rules() {
+        assert_eq!(create_position_from_fen("8/8/4k3/8/8/3RK3/8/8 w - - 99 80").outcome(), None);
+        assert_eq!(create_position_from_fen("8/8/4k3/8/8/3RK3/8/8 w - - 100 80").outcome(), Some(Outcome::FiftyMoves));
+        assert_eq!(create_position_from_fen("8/8/4k3/8/8/3RK3/8/8 w - - 150 80").outcome(), Some(Outcome::SeventyFiveMoves));
+    }
+
+    #[test]
+    fn test_outcome_repetition() {
+        let mut position = create_default_position();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        for uci in shuffle {
+            position.push(position.parse_uci_move(uci).unwrap());
+        }
+        assert_eq!(position.repetitions(), 2);
+        assert_eq!(position.outcome(), None);
+        for uci in shuffle {
+            position.push(position.parse_uci_move(uci).unwrap());
+        }
+        assert_eq!(position.repetitions(), 3);
+        assert_eq!(position.outcome(), Some(Outcome::ThreefoldRepetition));
+        for uci in shuffle.iter().chain(shuffle.iter()) {
+            position.push(position.parse_uci_move(uci).unwrap());
+        }
+        assert_eq!(position.outcome(), Some(Outcome::FivefoldRepetition));
+
+        // A pawn move in between means earlier positions can't come back
+        let mut position = create_default_position();
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8", "e2e4", "e7e5"] {
+            position.push(position.parse_uci_move(uci).unwrap());
+        }
+        assert_eq!(position.repetitions(), 1);
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        for (fen, insufficient) in [
+            ("8/8/4k3/8/8/4K3/8/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/4KN2/8/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/4KB2/8/8 w - - 0 1", true),
+            ("8/8/2b1k3/8/8/4KB2/8/8 w - - 0 1", true), // both bishops on light squares
+            ("8/8/3bk3/8/8/4KB2/8/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/4KNN1/8/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/4KN2/8/b7 w - - 0 1", false),
+            ("8/8/4k3/8/8/4KP2/8/8 w - - 0 1", false),
+            ("8/8/4k3/8/8/4KR2/8/8 w - - 0 1", false),
+        ] {
+            let position = create_position_from_fen(fen);
+            assert_eq!(position.is_insufficient_material(), insufficient, "{}", fen);
+            assert_eq!(position.outcome() == Some(Outcome::InsufficientMaterial), insufficient, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_parse_uci_move() {
+        let position = create_default_position();
+        let push = position.parse_uci_move("e2e4").unwrap();
+        assert!(push.is_double_pawn_push());
+        assert_eq!(push.to_uci(), "e2e4");
+        assert_eq!(position.parse_uci_move("e2e5"), Err(Error::IllegalMove("e2e5".to_string())));
+        for text in ["e2", "e2e4x", "z9e4", "e2e4qq", "e7e8k"] {
+            assert_eq!(position.parse_uci_move(text), Err(Error::InvalidUciMove(text.to_string())));
+        }
+
+        let kiwipete = create_position_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let castle = kiwipete.parse_uci_move("e1g1").unwrap();
+        assert_eq!(castle, Move::new(Square::E1, Square::G1, Move::KING_CASTLE));
+        assert_eq!(kiwipete.parse_uci_move("e1h1"), Ok(castle));
+        assert_eq!(kiwipete.parse_uci_move("e1a1"), Ok(Move::new(Square::E1, Square::C1, Move::QUEEN_CASTLE)));
+        assert!(kiwipete.parse_uci_move("e2a6").unwrap().is_capture());
+
+        let passant = create_position_from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        assert!(passant.parse_uci_move("e5f6").unwrap().is_en_passant());
+
+        let promotion = create_position_from_fen("4k3/8/8/8/8/8/1p5K/R7 b - - 0 1");
+        assert_eq!(promotion.parse_uci_move("b2a1n").unwrap().promotion(), Some(Promotion::Knight));
+        assert_eq!(promotion.parse_uci_move("b2a1Q").unwrap().promotion(), Some(Promotion::Queen));
+        assert_eq!(promotion.parse_uci_move("b2a1"), Err(Error::IllegalMove("b2a1".to_string())));
+
+        for position in [kiwipete, passant, promotion] {
+            for move_ in position.legal_moves() {
+                assert_eq!(position.parse_uci_move(&move_.to_uci()), Ok(move_));
+            }
+        }
+    }
+}
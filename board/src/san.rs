@@ -0,0 +1,207 @@
+use crate::moves::{get_moves, in_check, Move};
+use crate::position::Position;
+use crate::types::{File, PieceKind, Promotion, Rank, Square};
+use crate::Error;
+
+impl Position {
+    // Standard algebraic notation for a legal move, like Nbd7, exd5, e8=Q or O-O+
+    pub fn san(&self, move_: Move) -> String {
+        let mut san = if move_.is_castle() {
+            if move_.to().file() == File::G { "O-O".to_string() } else { "O-O-O".to_string() }
+        } else {
+            let kind = self.board.piece_at(move_.from()).map_or(PieceKind::Pawn, |piece| piece.kind);
+            let mut san = String::new();
+
+            if kind == PieceKind::Pawn {
+                if move_.is_capture() {
+                    san.push(move_.from().file().to_char());
+                }
+            } else {
+                san.push(kind.to_char().to_ascii_uppercase());
+
+                // Other pieces of the same kind that could also go there
+                let others: Vec<Square> = self.legal_moves().into_iter()
+                    .filter(|other| other.to() == move_.to() && other.from() != move_.from() &&
+                        self.board.piece_at(other.from()).is_some_and(|piece| piece.kind == kind))
+                    .map(|other| other.from())
+                    .collect();
+                if !others.is_empty() {
+                    if others.iter().all(|other| other.file() != move_.from().file()) {
+                        san.push(move_.from().file().to_char());
+                    } else if others.iter().all(|other| other.rank() != move_.from().rank()) {
+                        san.push(move_.from().rank().to_char());
+                    } else {
+                        san.push_str(&move_.from().to_string());
+                    }
+                }
+            }
+
+            if move_.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&move_.to().to_string());
+            if let Some(promotion) = move_.promotion() {
+                san.push('=');
+                san.push(promotion.to_char().to_ascii_uppercase());
+            }
+            san
+        };
+
+        // Check or mate
+        let mut board = self.board.clone();
+        let (mut castle_rights, mut en_passant) = (self.castle_rights, self.en_passant);
+        move_.make_move(&mut board, &mut castle_rights, &mut en_passant);
+        if in_check(&board, !self.side_to_move) {
+            san.push(if get_moves(&board, en_passant, castle_rights, !self.side_to_move).is_empty() { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    // Reads standard algebraic notation, also taking 0-0, a missing x, e.p. and annotations like !? or +
+    pub fn parse_san(&self, text: &str) -> Result<Move, Error> {
+        let invalid = || Error::InvalidSan(text.to_string());
+        let san = text.trim().trim_end_matches(['+', '#', '!', '?']);
+        let san = san.strip_suffix("e.p.").unwrap_or(san).trim_end();
+        if !san.is_ascii() {
+            return Err(invalid());
+        }
+
+        let legal = self.legal_moves();
+        let castle = match san {
+            "O-O" | "0-0" => Some(File::G),
+            "O-O-O" | "0-0-0" => Some(File::C),
+            _ => None,
+        };
+        if let Some(file) = castle {
+            return legal.into_iter()
+                .find(|move_| move_.is_castle() && move_.to().file() == file)
+                .ok_or_else(|| Error::IllegalMove(text.to_string()));
+        }
+
+        let mut chars: Vec<char> = san.chars().filter(|&c| c != 'x' && c != ':' && c != '-').collect();
+
+        let kind = match chars.first() {
+            Some(&c) if c.is_ascii_uppercase() => {
+                chars.remove(0);
+                PieceKind::from_char(c).filter(|&kind| kind != PieceKind::Pawn).ok_or_else(invalid)?
+            }
+            _ => PieceKind::Pawn,
+        };
+
+        // e8=Q and e8Q both promote
+        let mut promotion = None;
+        if kind == PieceKind::Pawn && chars.last().is_some_and(|c| c.is_ascii_uppercase()) {
+            promotion = Some(Promotion::from_char(chars.pop().unwrap()).ok_or_else(invalid)?);
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(invalid());
+        }
+        let to_text: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let to: Square = to_text.parse().map_err(|_| invalid())?;
+
+        // Whatever is left says which file and rank the piece comes from
+        let (mut from_file, mut from_rank) = (None, None);
+        for c in chars {
+            if let Some(file) = File::from_char(c).filter(|_| from_file.is_none() && from_rank.is_none()) {
+                from_file = Some(file);
+            } else if let Some(rank) = Rank::from_char(c).filter(|_| from_rank.is_none()) {
+                from_rank = Some(rank);
+            } else {
+                return Err(invalid());
+            }
+        }
+
+        let mut matches = legal.into_iter().filter(|move_| {
+            move_.to() == to && move_.promotion() == promotion && !move_.is_castle() &&
+                self.board.piece_at(move_.from()).is_some_and(|piece| piece.kind == kind) &&
+                from_file.is_none_or(|file| move_.from().file() == file) &&
+                from_rank.is_none_or(|rank| move_.from().rank() == rank)
+        });
+
+        match (matches.next(), matches.next()) {
+            (Some(move_), None) => Ok(move_),
+            (Some(_), Some(_)) => Err(Error::AmbiguousMove(text.to_string())),
+            (None, _) => Err(Error::IllegalMove(text.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::{create_default_position, create_position_from_fen};
+    use crate::types::Promotion;
+    use crate::Error;
+
+    fn san(fen: &str, uci: &str) -> String {
+        let position = create_position_from_fen(fen);
+        position.san(position.parse_uci_move(uci).unwrap())
+    }
+
+    #[test]
+    fn test_san() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san(kiwipete, "e1g1"), "O-O");
+        assert_eq!(san(kiwipete, "e1c1"), "O-O-O");
+        assert_eq!(san(kiwipete, "e2a6"), "Bxa6");
+        assert_eq!(san(kiwipete, "d5e6"), "dxe6");
+        assert_eq!(san(kiwipete, "a2a4"), "a4");
+        assert_eq!(san(kiwipete, "c3b1"), "Nb1");
+        assert_eq!(san(kiwipete, "e5g6"), "Nxg6");
+        assert_eq!(san(kiwipete, "f3f6"), "Qxf6");
+
+        // Knights on b1 and b5 share a file, rooks on a1 and a8 are told apart by rank
+        assert_eq!(san("4k3/8/8/1N6/8/8/8/1N2K3 w - - 0 1", "b1c3"), "N1c3");
+        assert_eq!(san("R7/8/7k/8/8/8/8/R3K3 w - - 0 1", "a1a4"), "R1a4");
+        // Three queens need both
+        assert_eq!(san("7k/8/8/8/Q1Q5/8/Q7/4K3 w - - 0 1", "a4b3"), "Qa4b3");
+
+        assert_eq!(san("4k3/8/8/8/8/8/1p5K/R7 b - - 0 1", "b2a1q"), "bxa1=Q");
+        assert_eq!(san("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", "e5f6"), "exf6");
+        assert_eq!(san("rnbqkbnr/ppppp2p/5p2/6p1/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3", "d1h5"), "Qh5#");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let kiwipete = create_position_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        for text in ["O-O", "0-0", "O-O+", "O-O!?"] {
+            assert_eq!(kiwipete.parse_san(text), kiwipete.parse_uci_move("e1g1"), "{}", text);
+        }
+        assert_eq!(kiwipete.parse_san("0-0-0"), kiwipete.parse_uci_move("e1c1"));
+        for text in ["Bxa6", "Ba6", "Be2a6", "Bxa6!", "B-a6"] {
+            assert_eq!(kiwipete.parse_san(text), kiwipete.parse_uci_move("e2a6"), "{}", text);
+        }
+        assert_eq!(kiwipete.parse_san("dxe6"), kiwipete.parse_uci_move("d5e6"));
+        assert_eq!(kiwipete.parse_san("de6"), kiwipete.parse_uci_move("d5e6"));
+        assert_eq!(kiwipete.parse_san("Qxf6?"), kiwipete.parse_uci_move("f3f6"));
+        assert_eq!(kiwipete.parse_san("Ncb1"), kiwipete.parse_uci_move("c3b1"));
+        let knights = create_position_from_fen("4k3/8/8/1N6/8/8/8/1N2K3 w - - 0 1");
+        assert_eq!(knights.parse_san("Nc3"), Err(Error::AmbiguousMove("Nc3".to_string())));
+        assert_eq!(knights.parse_san("N1c3"), knights.parse_uci_move("b1c3"));
+        assert_eq!(knights.parse_san("Nbc3"), Err(Error::AmbiguousMove("Nbc3".to_string())));
+        assert_eq!(kiwipete.parse_san("Ke3"), Err(Error::IllegalMove("Ke3".to_string())));
+        for text in ["", "Z4", "Nz9", "e9", "Pe4", "Kx"] {
+            assert_eq!(kiwipete.parse_san(text), Err(Error::InvalidSan(text.to_string())), "{}", text);
+        }
+
+        let promotion = create_position_from_fen("4k3/8/8/8/8/8/1p5K/R7 b - - 0 1");
+        assert_eq!(promotion.parse_san("bxa1=N").unwrap().promotion(), Some(Promotion::Knight));
+        assert_eq!(promotion.parse_san("bxa1Q").unwrap().promotion(), Some(Promotion::Queen));
+        assert!(promotion.parse_san("bxa1").is_err());
+
+        let passant = create_position_from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        assert!(passant.parse_san("exf6 e.p.").unwrap().is_en_passant());
+
+        // Every legal move's san reads back as the same move
+        for position in [create_default_position(), kiwipete, promotion, passant, knights] {
+            for move_ in position.legal_moves() {
+                assert_eq!(position.parse_san(&position.san(move_)), Ok(move_), "{}", position.san(move_));
+            }
+        }
+    }
+}
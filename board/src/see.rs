@@ -0,0 +1,118 @@
+use crate::magic;
+use crate::moves::Move;
+use crate::position::Position;
+use crate::types::{Color, PieceKind};
+
+// Indexed by PieceKind, the king is worth more than anything it could win
+pub const SEE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 20_000];
+
+fn value(kind: PieceKind) -> i32 {
+    SEE_VALUES[kind.index()]
+}
+
+// Material the side to move comes out with after the best sequence of captures on the move's target
+// square, each side may stop capturing whenever it likes. Pins are not looked at.
+pub fn see(position: &Position, move_: Move) -> i32 {
+    if move_.is_castle() {
+        return 0;
+    }
+
+    let board = &position.board;
+    let (from, to) = (move_.from(), move_.to());
+    let Some(mut moving) = board.piece_at(from).map(|piece| piece.kind) else {
+        return 0;
+    };
+
+    let mut occupancy = board.all_pieces() & !from.bit();
+    let mut gain = [0i32; 32];
+    gain[0] = if move_.is_en_passant() {
+        occupancy &= !to.offset(0, if position.side_to_move == Color::White { -1 } else { 1 }).unwrap().bit();
+        value(PieceKind::Pawn)
+    } else {
+        board.piece_at(to).map_or(0, |piece| value(piece.kind))
+    };
+    if let Some(promotion) = move_.promotion() {
+        gain[0] += value(promotion.kind()) - value(PieceKind::Pawn);
+        moving = promotion.kind();
+    }
+
+    let bishops = board.pieces(PieceKind::Bishop) | board.pieces(PieceKind::Queen);
+    let rooks = board.pieces(PieceKind::Rook) | board.pieces(PieceKind::Queen);
+    let mut attackers = board.attackers_to(to, occupancy) & occupancy;
+    let mut side = !position.side_to_move;
+    let mut depth = 0;
+
+    // Least valuable attacker goes first
+    while let Some((kind, attacker)) = PieceKind::ALL.iter()
+        .map(|&kind| (kind, attackers & board.colored(side) & board.pieces(kind)))
+        .find(|&(_, attacker)| attacker != 0) {
+        depth += 1;
+        gain[depth] = value(moving) - gain[depth - 1]; // what side has if the other side doesn't take back
+
+        occupancy &= !(attacker & attacker.wrapping_neg()); // lowest bit
+        // Sliders lined up behind the attacker join in
+        if matches!(kind, PieceKind::Pawn | PieceKind::Bishop | PieceKind::Queen) {
+            attackers |= magic::bishop_attacks(to, occupancy) & bishops;
+        }
+        if matches!(kind, PieceKind::Rook | PieceKind::Queen) {
+            attackers |= magic::rook_attacks(to, occupancy) & rooks;
+        }
+        attackers &= occupancy;
+
+        moving = kind;
+        side = !side;
+    }
+
+    // Each side picks between stopping and carrying on
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+
+    gain[0]
+}
+
+// Whether see(position, move_) is at least threshold
+pub fn see_ge(position: &Position, move_: Move, threshold: i32) -> bool {
+    see(position, move_) >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::create_position_from_fen;
+    use crate::see::{see, see_ge};
+
+    #[test]
+    fn test_see() {
+        for (fen, uci, expected) in [
+            ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", 100), // free pawn
+            ("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5", 100),
+            ("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1", "d2d5", -800), // defended pawn
+            ("4k3/8/3r4/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5", 100), // second rook behind the first
+            ("4k3/3r4/3r4/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5", -400), // both sides doubled
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100), // en passant
+            ("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q", 1100), // promotion with capture
+            ("3k4/3r4/8/8/8/8/8/3QK3 w - - 0 1", "d1d7", -400), // the king takes back
+            ("3k4/3r4/8/8/8/8/3Q4/3RK3 w - - 0 1", "d2d7", 500), // the king can't take back
+            ("4k3/8/8/2p5/8/8/4N3/4K3 w - - 0 1", "e2d4", -300), // quiet move onto an attacked square
+            ("4k3/8/8/8/8/8/4N3/4K3 w - - 0 1", "e2d4", 0),
+            ("4k3/8/1b6/8/3p4/4P3/8/4K1Q1 w - - 0 1", "e3d4", 100), // the queen x-rays through the pawn
+        ] {
+            let position = create_position_from_fen(fen);
+            assert_eq!(see(&position, position.parse_uci_move(uci).unwrap()), expected, "{} in {}", uci, fen);
+        }
+    }
+
+    #[test]
+    fn test_see_ge() {
+        let position = create_position_from_fen("4k3/8/3r4/3p4/8/8/3R4/3RK3 w - - 0 1");
+        let move_ = position.parse_uci_move("d2d5").unwrap();
+        assert!(see_ge(&position, move_, 0));
+        assert!(see_ge(&position, move_, 100));
+        assert!(!see_ge(&position, move_, 101));
+
+        let position = create_position_from_fen("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1");
+        assert!(!see_ge(&position, position.parse_uci_move("d2d5").unwrap(), 0));
+        assert!(see_ge(&position, position.parse_uci_move("d2d5").unwrap(), -800));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::types::{Color, Square};
+
+// All generated at compile time
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+
+pub static KNIGHT_ATTACKS: [u64; 64] = step_attacks(&KNIGHT_STEPS);
+pub static KING_ATTACKS: [u64; 64] = step_attacks(&KING_STEPS);
+pub static PAWN_ATTACKS: [[u64; 64]; 2] = [step_attacks(&[(-1, 1), (1, 1)]), step_attacks(&[(-1, -1), (1, -1)])];
+
+// Squares strictly between two squares on the same rank, file or diagonal, empty otherwise
+pub static BETWEEN: [[u64; 64]; 64] = generate_lines(false);
+// The whole rank, file or diagonal through two squares, empty when they don't share one
+pub static LINE: [[u64; 64]; 64] = generate_lines(true);
+
+const fn offset(square: usize, files: i32, ranks: i32) -> Option<usize> {
+    let file = (square % 8) as i32 + files;
+    let rank = (square / 8) as i32 + ranks;
+    if file < 0 || file > 7 || rank < 0 || rank > 7 {
+        None
+    } else {
+        Some((rank * 8 + file) as usize)
+    }
+}
+
+const fn step_attacks(steps: &[(i32, i32)]) -> [u64; 64] {
+    let mut attacks = [0u64; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut i = 0;
+        while i < steps.len() {
+            if let Some(to) = offset(square, steps[i].0, steps[i].1) {
+                attacks[square] |= 1u64 << to;
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+
+    attacks
+}
+
+// Every square reached walking from square in the direction, not including square
+const fn ray(square: usize, direction: (i32, i32)) -> u64 {
+    let mut bits = 0;
+    let mut loc = square;
+    while let Some(next) = offset(loc, direction.0, direction.1) {
+        bits |= 1u64 << next;
+        loc = next;
+    }
+
+    bits
+}
+
+const fn generate_lines(full_line: bool) -> [[u64; 64]; 64] {
+    let mut lines = [[0u64; 64]; 64];
+    let mut from = 0;
+    while from < 64 {
+        let mut i = 0;
+        while i < KING_STEPS.len() {
+            let direction = KING_STEPS[i];
+            let line = ray(from, direction) | ray(from, (-direction.0, -direction.1)) | 1u64 << from;
+
+            let mut between = 0;
+            let mut loc = from;
+            while let Some(to) = offset(loc, direction.0, direction.1) {
+                lines[from][to] = if full_line { line } else { between };
+                between |= 1u64 << to;
+                loc = to;
+            }
+            i += 1;
+        }
+        from += 1;
+    }
+
+    lines
+}
+
+pub fn knight_attacks(square: Square) -> u64 {
+    KNIGHT_ATTACKS[square.index()]
+}
+
+pub fn king_attacks(square: Square) -> u64 {
+    KING_ATTACKS[square.index()]
+}
+
+// Squares a pawn of the color standing on square attacks
+pub fn pawn_attacks(color: Color, square: Square) -> u64 {
+    PAWN_ATTACKS[color.index()][square.index()]
+}
+
+pub fn between(a: Square, b: Square) -> u64 {
+    BETWEEN[a.index()][b.index()]
+}
+
+pub fn line(a: Square, b: Square) -> u64 {
+    LINE[a.index()][b.index()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{bits, square_from_algebraic};
+    use crate::tables::{between, king_attacks, knight_attacks, line, pawn_attacks};
+    use crate::types::{squares, Color, Square};
+
+    #[test]
+    fn test_step_attacks() {
+        assert_eq!(knight_attacks(Square::A1), bits(&["b3", "c2"]));
+        assert_eq!(knight_attacks(square_from_algebraic("e4")).count_ones(), 8);
+        assert_eq!(king_attacks(Square::H8), bits(&["g8", "g7", "h7"]));
+        assert_eq!(pawn_attacks(Color::White, square_from_algebraic("a2")), bits(&["b3"]));
+        assert_eq!(pawn_attacks(Color::Black, square_from_algebraic("e5")), bits(&["d4", "f4"]));
+        assert_eq!(pawn_attacks(Color::White, Square::H8), 0);
+    }
+
+    #[test]
+    fn test_between_and_line() {
+        let sq = square_from_algebraic;
+        assert_eq!(between(sq("a1"), sq("d4")), bits(&["b2", "c3"]));
+        assert_eq!(between(sq("d4"), sq("a1")), bits(&["b2", "c3"]));
+        assert_eq!(between(sq("e1"), sq("e8")), bits(&["e2", "e3", "e4", "e5", "e6", "e7"]));
+        assert_eq!(between(sq("e1"), sq("f1")), 0);
+        assert_eq!(between(sq("a1"), sq("b3")), 0);
+        assert_eq!(line(sq("c3"), sq("e5")), bits(&["a1", "b2", "c3", "d4", "e5", "f6", "g7", "h8"]));
+        assert_eq!(line(sq("b1"), sq("g1")), 0xFF);
+        assert_eq!(line(sq("a1"), sq("b3")), 0);
+
+        for a in squares(!0) {
+            for b in squares(!0) {
+                assert_eq!(between(a, b), between(b, a));
+                assert_eq!(line(a, b), line(b, a));
+                assert_eq!(between(a, b) & !line(a, b), 0);
+                if line(a, b) != 0 {
+                    assert_eq!(line(a, b) & (a.bit() | b.bit()), a.bit() | b.bit());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+use std::str::FromStr;
+use crate::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    // Index of the color bitboard in Board::bitboards
+    pub const fn bitboard(self) -> usize {
+        6 + self as usize
+    }
+}
+
+impl Not for Color {
+    type Output = Color;
+
+    fn not(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum File {
+    A, B, C, D, E, F, G, H,
+}
+
+impl File {
+    pub const ALL: [File; 8] = [File::A, File::B, File::C, File::D, File::E, File::F, File::G, File::H];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    pub const fn from_index(index: usize) -> File {
+        File::ALL[index]
+    }
+
+    pub fn from_char(c: char) -> Option<File> {
+        match c {
+            'a'..='h' => Some(File::from_index(c as usize - 'a' as usize)),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        (b'a' + self as u8) as char
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Rank {
+    First, Second, Third, Fourth, Fifth, Sixth, Seventh, Eighth,
+}
+
+impl Rank {
+    pub const ALL: [Rank; 8] = [Rank::First, Rank::Second, Rank::Third, Rank::Fourth,
+        Rank::Fifth, Rank::Sixth, Rank::Seventh, Rank::Eighth];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    pub const fn from_index(index: usize) -> Rank {
+        Rank::ALL[index]
+    }
+
+    pub fn from_char(c: char) -> Option<Rank> {
+        match c {
+            '1'..='8' => Some(Rank::from_index(c as usize - '1' as usize)),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        (b'1' + self as u8) as char
+    }
+
+    // The rank as seen from the given side, so the fourth rank for black is the fifth for white
+    pub const fn relative(self, color: Color) -> Rank {
+        match color {
+            Color::White => self,
+            Color::Black => Rank::from_index(7 - self as usize),
+        }
+    }
+}
+
+// Always a valid square, a1 is 0 and h8 is 63
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Square(u8);
+
+impl Square {
+    pub const A1: Square = Square(0);
+    pub const B1: Square = Square(1);
+    pub const C1: Square = Square(2);
+    pub const D1: Square = Square(3);
+    pub const E1: Square = Square(4);
+    pub const F1: Square = Square(5);
+    pub const G1: Square = Square(6);
+    pub const H1: Square = Square(7);
+    pub const A8: Square = Square(56);
+    pub const B8: Square = Square(57);
+    pub const C8: Square = Square(58);
+    pub const D8: Square = Square(59);
+    pub const E8: Square = Square(60);
+    pub const F8: Square = Square(61);
+    pub const G8: Square = Square(62);
+    pub const H8: Square = Square(63);
+
+    pub const fn new(file: File, rank: Rank) -> Square {
+        Square((rank as u8) * 8 + file as u8)
+    }
+
+    pub const fn from_index(index: usize) -> Square {
+        assert!(index < 64, "Invalid square");
+        Square(index as u8)
+    }
+
+    pub const fn try_from_index(index: usize) -> Option<Square> {
+        if index < 64 { Some(Square(index as u8)) } else { None }
+    }
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub const fn bit(self) -> u64 {
+        1u64 << self.0
+    }
+
+    pub const fn file(self) -> File {
+        File::from_index((self.0 % 8) as usize)
+    }
+
+    pub const fn rank(self) -> Rank {
+        Rank::from_index((self.0 / 8) as usize)
+    }
+
+    pub fn offset(self, files: i32, ranks: i32) -> Option<Square> {
+        let file = self.file().index() as i32 + files;
+        let rank = self.rank().index() as i32 + ranks;
+        if (0..8).contains(&file) && (0..8).contains(&rank) {
+            Some(Square((rank * 8 + file) as u8))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file().to_char(), self.rank().to_char())
+    }
+}
+
+impl FromStr for Square {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Square, Error> {
+        let mut chars = s.chars();
+        match (chars.next().and_then(File::from_char), chars.next().and_then(Rank::from_char), chars.next()) {
+            (Some(file), Some(rank), None) => Ok(Square::new(file, rank)),
+            _ => Err(Error::InvalidSquare(s.to_string())),
+        }
+    }
+}
+
+// Iterates the squares of the set bits, lowest first
+pub struct Squares(u64);
+
+pub fn squares(bitboard: u64) -> Squares {
+    Squares(bitboard)
+}
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = Square(self.0.trailing_zeros() as u8);
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+// The discriminants are the indexes of the piece bitboards in Board::bitboards
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 6] = [PieceKind::Pawn, PieceKind::Knight, PieceKind::Bishop,
+        PieceKind::Rook, PieceKind::Queen, PieceKind::King];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_char(c: char) -> Option<PieceKind> {
+        match c.to_ascii_lowercase() {
+            'p' => Some(PieceKind::Pawn),
+            'n' => Some(PieceKind::Knight),
+            'b' => Some(PieceKind::Bishop),
+            'r' => Some(PieceKind::Rook),
+            'q' => Some(PieceKind::Queen),
+            'k' => Some(PieceKind::King),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        ['p', 'n', 'b', 'r', 'q', 'k'][self as usize]
+    }
+}
+
+// The pieces a pawn can promote to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Promotion {
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+}
+
+impl Promotion {
+    pub const ALL: [Promotion; 4] = [Promotion::Queen, Promotion::Rook, Promotion::Bishop, Promotion::Knight];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    // Knight is 0 and queen is 3, the order packed moves store them in
+    pub const fn from_index(index: usize) -> Promotion {
+        match index & 3 {
+            0 => Promotion::Knight,
+            1 => Promotion::Bishop,
+            2 => Promotion::Rook,
+            _ => Promotion::Queen,
+        }
+    }
+
+    pub const fn kind(self) -> PieceKind {
+        match self {
+            Promotion::Knight => PieceKind::Knight,
+            Promotion::Bishop => PieceKind::Bishop,
+            Promotion::Rook => PieceKind::Rook,
+            Promotion::Queen => PieceKind::Queen,
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Promotion> {
+        match c.to_ascii_lowercase() {
+            'n' => Some(Promotion::Knight),
+            'b' => Some(Promotion::Bishop),
+            'r' => Some(Promotion::Rook),
+            'q' => Some(Promotion::Queen),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        self.kind().to_char()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceKind,
+}
+
+impl Piece {
+    pub const fn new(color: Color, kind: PieceKind) -> Piece {
+        Piece { color, kind }
+    }
+
+    // FEN letter, uppercase for white
+    pub fn from_char(c: char) -> Option<Piece> {
+        let kind = PieceKind::from_char(c)?;
+        Some(Piece::new(if c.is_ascii_uppercase() { Color::White } else { Color::Black }, kind))
+    }
+
+    pub fn to_char(self) -> char {
+        match self.color {
+            Color::White => self.kind.to_char().to_ascii_uppercase(),
+            Color::Black => self.kind.to_char(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct CastleRights(u8);
+
+impl CastleRights {
+    pub const NONE: CastleRights = CastleRights(0);
+    pub const WHITE_KING_SIDE: CastleRights = CastleRights(0b0001);
+    pub const WHITE_QUEEN_SIDE: CastleRights = CastleRights(0b0010);
+    pub const BLACK_QUEEN_SIDE: CastleRights = CastleRights(0b0100);
+    pub const BLACK_KING_SIDE: CastleRights = CastleRights(0b1000);
+    pub const WHITE: CastleRights = CastleRights(0b0011);
+    pub const BLACK: CastleRights = CastleRights(0b1100);
+    pub const ALL: CastleRights = CastleRights(0b1111);
+
+    pub const fn from_bits(bits: u8) -> CastleRights {
+        CastleRights(bits & 0b1111)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn for_color(color: Color) -> CastleRights {
+        match color {
+            Color::White => CastleRights::WHITE,
+            Color::Black => CastleRights::BLACK,
+        }
+    }
+
+    pub const fn contains(self, other: CastleRights) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn intersects(self, other: CastleRights) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn insert(&mut self, other: CastleRights) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: CastleRights) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for CastleRights {
+    type Output = CastleRights;
+
+    fn bitor(self, rhs: CastleRights) -> CastleRights {
+        CastleRights(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for CastleRights {
+    fn bitor_assign(&mut self, rhs: CastleRights) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for CastleRights {
+    type Output = CastleRights;
+
+    fn bitand(self, rhs: CastleRights) -> CastleRights {
+        CastleRights(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for CastleRights {
+    fn bitand_assign(&mut self, rhs: CastleRights) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl Not for CastleRights {
+    type Output = CastleRights;
+
+    fn not(self) -> CastleRights {
+        CastleRights(!self.0 & 0b1111)
+    }
+}
+
+// FEN castling field, "KQkq" or "-"
+impl fmt::Display for CastleRights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "-");
+        }
+        for (right, letter) in [(CastleRights::WHITE_KING_SIDE, 'K'), (CastleRights::WHITE_QUEEN_SIDE, 'Q'),
+            (CastleRights::BLACK_KING_SIDE, 'k'), (CastleRights::BLACK_QUEEN_SIDE, 'q')] {
+            if self.contains(right) {
+                write!(f, "{}", letter)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for CastleRights {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CastleRights, Error> {
+        let mut rights = CastleRights::NONE;
+        if s == "-" {
+            return Ok(rights);
+        }
+        if s.is_empty() {
+            return Err(Error::InvalidCastling(s.to_string()));
+        }
+
+        for c in s.chars() {
+            let right = match c {
+                'K' => CastleRights::WHITE_KING_SIDE,
+                'Q' => CastleRights::WHITE_QUEEN_SIDE,
+                'k' => CastleRights::BLACK_KING_SIDE,
+                'q' => CastleRights::BLACK_QUEEN_SIDE,
+                _ => { return Err(Error::InvalidCastling(s.to_string())); }
+            };
+            if rights.intersects(right) {
+                return Err(Error::InvalidCastling(s.to_string())); // repeated letter
+            }
+            rights |= right;
+        }
+
+        Ok(rights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{squares, CastleRights, Color, File, Piece, PieceKind, Rank, Square};
+
+    #[test]
+    fn test_square() {
+        let square: Square = "e4".parse().unwrap();
+        assert_eq!(square, Square::new(File::E, Rank::Fourth));
+        assert_eq!(square.index(), 28);
+        assert_eq!(square.to_string(), "e4");
+        assert_eq!(square.offset(1, 1), Some(Square::new(File::F, Rank::Fifth)));
+        assert_eq!(Square::H8.offset(1, 0), None);
+        assert!("e9".parse::<Square>().is_err());
+        assert_eq!(Square::try_from_index(64), None);
+        assert_eq!(Rank::Second.relative(Color::Black), Rank::Seventh);
+    }
+
+    #[test]
+    fn test_squares() {
+        let found: Vec<Square> = squares(Square::A1.bit() | Square::E1.bit() | Square::H8.bit()).collect();
+        assert_eq!(found, vec![Square::A1, Square::E1, Square::H8]);
+    }
+
+    #[test]
+    fn test_pieces() {
+        assert_eq!(Piece::from_char('N'), Some(Piece::new(Color::White, PieceKind::Knight)));
+        assert_eq!(Piece::from_char('q'), Some(Piece::new(Color::Black, PieceKind::Queen)));
+        assert_eq!(Piece::from_char('x'), None);
+        assert_eq!(Piece::new(Color::Black, PieceKind::King).to_char(), 'k');
+    }
+
+    #[test]
+    fn test_castle_rights() {
+        let rights: CastleRights = "Kq".parse().unwrap();
+        assert_eq!(rights, CastleRights::WHITE_KING_SIDE | CastleRights::BLACK_QUEEN_SIDE);
+        assert_eq!(rights.to_string(), "Kq");
+        assert_eq!(CastleRights::NONE.to_string(), "-");
+        assert_eq!(!CastleRights::WHITE, CastleRights::BLACK);
+        assert!("KK".parse::<CastleRights>().is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::board::Board;
+use crate::types::{squares, CastleRights, Color, Piece, PieceKind, Square};
+
+// 12 piece types * 64 squares, then side to move, 16 castle right combinations and 8 en passant files
+const PIECE_KEYS: usize = 0;
+const SIDE_KEY: usize = 12 * 64;
+const CASTLE_KEYS: usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLE_KEYS + 16;
+const KEY_COUNT: usize = EN_PASSANT_KEYS + 8;
+
+const KEYS: [u64; KEY_COUNT] = generate_keys();
+
+// splitmix64, fixed seed so hashes are the same between runs
+const fn generate_keys() -> [u64; KEY_COUNT] {
+    let mut keys = [0u64; KEY_COUNT];
+    let mut state: u64 = 0x5275_6368_656E_6769;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+
+    // Combined rights are the XOR of the single rights, so no rights at all hashes to zero
+    keys[CASTLE_KEYS] = 0;
+    let mut rights = 3;
+    while rights < 16 {
+        if (rights as u32).count_ones() < 2 {
+            rights += 1;
+            continue;
+        }
+        keys[CASTLE_KEYS + rights] = 0;
+        let mut bit = 0;
+        while bit < 4 {
+            if rights & (1 << bit) != 0 {
+                keys[CASTLE_KEYS + rights] ^= keys[CASTLE_KEYS + (1 << bit)];
+            }
+            bit += 1;
+        }
+        rights += 1;
+    }
+
+    keys
+}
+
+pub fn piece_key(piece: Piece, square: Square) -> u64 {
+    KEYS[PIECE_KEYS + (piece.color.index() * 6 + piece.kind.index()) * 64 + square.index()]
+}
+
+pub fn side_key(color: Color) -> u64 {
+    match color {
+        Color::White => 0,
+        Color::Black => KEYS[SIDE_KEY],
+    }
+}
+
+pub fn castle_key(castle_rights: CastleRights) -> u64 {
+    KEYS[CASTLE_KEYS + castle_rights.bits() as usize]
+}
+
+// Only hashed when a pawn of the side to move can actually take, otherwise the same
+// position reached by a single and a double pawn push would get different keys
+pub fn en_passant_key(board: &Board, en_passant: Option<Square>, color: Color) -> u64 {
+    let Some(en_passant) = en_passant else {
+        return 0;
+    };
+
+    let pushed_rank = if color == Color::White { -1 } else { 1 };
+    let neighbours = [-1, 1].into_iter()
+        .filter_map(|file| en_passant.offset(file, pushed_rank))
+        .fold(0, |bits, square| bits | square.bit());
+
+    if board.piece_bitboard(Piece::new(color, PieceKind::Pawn)) & neighbours != 0 {
+        KEYS[EN_PASSANT_KEYS + en_passant.file().index()]
+    } else {
+        0
+    }
+}
+
+// Hash of the piece placement only, this is what Board::hash holds
+pub fn compute_board_hash(board: &Board) -> u64 {
+    let mut hash = 0;
+    for square in squares(board.all_pieces()) {
+        if let Some(piece) = board.piece_at(square) {
+            hash ^= piece_key(piece, square);
+        }
+    }
+
+    hash
+}
+
+// Full key using the incrementally updated Board::hash
+pub fn hash(board: &Board, color: Color, castle_rights: CastleRights, en_passant: Option<Square>) -> u64 {
+    board.hash ^ side_key(color) ^ castle_key(castle_rights) ^ en_passant_key(board, en_passant, color)
+}
+
+// Full key from scratch, used to verify the incremental one
+pub fn compute_hash(board: &Board, color: Color, castle_rights: CastleRights, en_passant: Option<Square>) -> u64 {
+    compute_board_hash(board) ^ side_key(color) ^ castle_key(castle_rights) ^ en_passant_key(board, en_passant, color)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::moves::get_moves;
+    use crate::position::create_position_from_fen;
+    use crate::types::{CastleRights, Color, Square};
+    use crate::zobrist::{compute_board_hash, compute_hash, hash};
+
+    fn play(fen: &str, moves: &[&str]) -> u64 {
+        let mut position = create_position_from_fen(fen);
+        for uci in moves {
+            let move_ = position.parse_uci_move(uci).unwrap();
+            position.push(move_);
+        }
+
+        hash(&position.board, position.side_to_move, position.castle_rights, position.en_passant)
+    }
+
+    fn verify(board: &mut Board, color: Color, mut castle_rights: CastleRights, mut en_passant: Option<Square>, depth: u32) {
+        assert_eq!(board.hash, compute_board_hash(board));
+        if depth == 0 {
+            return;
+        }
+
+        for move_ in get_moves(board, en_passant, castle_rights, color) {
+            let before = board.hash;
+            let undo = move_.make_move(board, &mut castle_rights, &mut en_passant);
+            assert_eq!(hash(board, !color, castle_rights, en_passant), compute_hash(board, !color, castle_rights, en_passant));
+            verify(board, !color, castle_rights, en_passant, depth - 1);
+            move_.unmake_move(board, &mut castle_rights, &mut en_passant, undo);
+            assert_eq!(board.hash, before);
+        }
+    }
+
+    #[test]
+    fn test_incremental_hash() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let mut position = create_position_from_fen(fen);
+            verify(&mut position.board, position.side_to_move, position.castle_rights, position.en_passant, 3);
+        }
+    }
+
+    #[test]
+    fn test_transpositions() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(play(start, &["g1f3", "g8f6", "f3g1", "f6g8"]), play(start, &[]));
+        // e2e4 played second leaves an en passant square no black pawn can use
+        assert_eq!(play(start, &["e2e4", "e7e5", "g1f3"]), play(start, &["g1f3", "e7e5", "e2e4"]));
+        assert_ne!(play(start, &["g1f3", "g8f6", "f3g1"]), play(start, &["g1f3", "g8f6"]));
+    }
+
+    #[test]
+    fn test_state_keys() {
+        let position = create_position_from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        let key = hash(&position.board, Color::White, position.castle_rights, position.en_passant);
+        assert_ne!(key, hash(&position.board, Color::Black, position.castle_rights, position.en_passant));
+        assert_ne!(key, hash(&position.board, Color::White, CastleRights::WHITE | CastleRights::BLACK_QUEEN_SIDE, position.en_passant));
+        assert_ne!(key, hash(&position.board, Color::White, position.castle_rights, None));
+    }
+
+    #[test]
+    fn test_castle_rights_lost_on_rook_capture() {
+        // Bxa8 takes the rook, black should lose queen side castling
+        let before = play("r3k2r/8/8/8/8/8/6B1/4K3 w kq - 0 1", &["g2a8"]);
+        let expected = create_position_from_fen("B3k2r/8/8/8/8/8/8/4K3 b k - 0 1");
+        assert_eq!(before, expected.hash());
+    }
+}
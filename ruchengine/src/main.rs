@@ -1,11 +1,17 @@
 use std::time::Instant;
 use board::board::{print_bitboard, square_from_algebraic, square_to_algebraic};
-use board::count_moves;
-use board::count_moves_no_threads;
+use board::{perft, perft_hashed, perft_parallel, PerftTable};
+use board::position::create_position_from_fen;
+use board::types::Square;
 
-fn test(string: &str, depth: u32, expected: u64, castle_rights: u32, white: bool, en_passant: &str) {
-    let mut board = board::board::create_board_from_string(string);
-    let perft = count_moves(&mut board, castle_rights, white, if en_passant.len() == 2 { board::board::square_from_algebraic(en_passant) } else { 0 }, depth);
+// Worker count for the threaded runs from the first argument, 0 or nothing uses every core
+fn threads() -> usize {
+    std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(0)
+}
+
+fn test(fen: &str, depth: u32, expected: u64) {
+    let position = create_position_from_fen(fen);
+    let perft = perft_parallel(&position, depth + 1, threads(), None).total;
     println!("Promotion Count: {}, Capture Count: {}, Check Count: {}, Castle Count {}, En Passant Count {}",
              perft.promotion_count, perft.capture_count, perft.check_count, perft.castle_count, perft.en_passant_count);
     println!("Total Count: {}", perft.total_count);
@@ -14,9 +20,10 @@ fn test(string: &str, depth: u32, expected: u64, castle_rights: u32, white: bool
     }
 }
 
-fn test_no_thread(string: &str, depth: u32, expected: u64, castle_rights: u32, white: bool, en_passant: &str) {
-    let mut board = board::board::create_board_from_string(string);
-    let perft = count_moves_no_threads(&mut board, castle_rights, white, if en_passant.len() == 2 { board::board::square_from_algebraic(en_passant) } else { 0 }, depth);
+fn test_no_thread(fen: &str, depth: u32, expected: u64) {
+    let mut position = create_position_from_fen(fen);
+    let report = perft(&mut position, depth + 1, Some(&mut |move_, stats| println!("{}: {}", move_, stats.total_count)));
+    let perft = report.total;
     println!("Promotion Count: {}, Capture Count: {}, Check Count: {}, Castle Count {}, En Passant Count {}",
              perft.promotion_count, perft.capture_count, perft.check_count, perft.castle_count, perft.en_passant_count);
     println!("Total Count: {}", perft.total_count);
@@ -25,9 +32,20 @@ fn test_no_thread(string: &str, depth: u32, expected: u64, castle_rights: u32, w
     }
 }
 
+// Node count only, through a hash table, for the runs too deep to do in full
+fn test_hashed(fen: &str, depth: u32, expected: u64) {
+    let mut position = create_position_from_fen(fen);
+    let table = PerftTable::new(256);
+    let nodes = perft_hashed(&mut position, depth + 1, &table);
+    println!("Total Count: {}", nodes);
+    if expected != 0 {
+        assert_eq!(nodes, expected);
+    }
+}
+
 fn main() {
     println!("Hello, world!");
-    println!("{}, {}", square_to_algebraic(4), square_to_algebraic(22));
+    println!("{}, {}", square_to_algebraic(Square::from_index(4)), square_to_algebraic(Square::from_index(22)));
     print_bitboard(1073741824, 'X', '.');
     print_bitboard(9331893833473007105, 'X', '.');
     println!("b1 {} c1 {} h1 {} h8 {}", square_from_algebraic("b1"),
@@ -60,44 +78,44 @@ fn main() {
     //              square_to_algebraic(move_.to), move_.capture);
     // }
     let start_time = Instant::now();
-    test("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R", 4, 89_941_194, 0b0011, true, "");
+    test("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 4, 89_941_194);
     println!("Time elapsed: {:?}", start_time.elapsed());
 
     let start_time = Instant::now();
-    test_no_thread("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R", 4, 89_941_194, 0b0011, true, "");
+    test_no_thread("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 4, 89_941_194);
     println!("Time elapsed: {:?}", start_time.elapsed());
 
     let start_time = Instant::now();
-    test("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R", 5, 8_031_647_685, 0b1111, true, "");
+    test("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 5, 8_031_647_685);
     println!("Time elapsed: {:?}", start_time.elapsed());
 
     let start_time = Instant::now();
-    test_no_thread("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R", 5, 8_031_647_685, 0b1111, true, "");
+    test_no_thread("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 5, 8_031_647_685);
     println!("Time elapsed: {:?}", start_time.elapsed());
 
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 0, 20, 0b1111, true, "");
+    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0, 20);
     println!("Time elapsed: {:?}", start_time.elapsed());
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 1, 400, 0b1111, true, "");
+    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 1, 400);
     println!("Time elapsed: {:?}", start_time.elapsed());
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 2, 8_902, 0b1111, true, "");
+    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 2, 8_902);
     println!("Time elapsed: {:?}", start_time.elapsed());
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 3, 197_281, 0b1111, true, "");
+    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 3, 197_281);
     println!("Time elapsed: {:?}", start_time.elapsed());
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 4, 4_865_609, 0b1111, true, "");
+    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4, 4_865_609);
     println!("Time elapsed: {:?}", start_time.elapsed());
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 5, 119_060_324, 0b1111, true, "");
+    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 5, 119_060_324);
     println!("Time elapsed: {:?}", start_time.elapsed());
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 6, 3_195_901_860, 0b1111, true, "");
+    test_hashed("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 6, 3_195_901_860);
     println!("Time elapsed: {:?}", start_time.elapsed());
     let start_time = Instant::now();
-    test("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", 7, 84_998_978_956, 0b1111, true, "");
+    test_hashed("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 7, 84_998_978_956);
     println!("Time elapsed: {:?}", start_time.elapsed());
 }
 